@@ -61,8 +61,18 @@ const buildJob = job("build", {
       if: isRelease,
       run: "cargo build --target wasm32-unknown-unknown --features wasm --release",
     },
+    {
+      name: "Build npm API",
+      if: isRelease,
+      run: [
+        "cargo build --target wasm32-unknown-unknown --features js --release --target-dir target/js",
+        "cargo install wasm-bindgen-cli --version 0.2.100 --locked",
+        "wasm-bindgen --target nodejs --out-dir deployment/npm/api --out-name api target/js/wasm32-unknown-unknown/release/dprint_plugin_dockerfile.wasm",
+      ],
+    },
 
     { name: "Test debug", if: isDebug, run: "cargo test" },
+    { name: "Test features", if: isDebug, run: "cargo test --features wasm,cli,serde,js" },
     { name: "Test release", if: isRelease, run: "cargo test --release" },
 
     getTagVersion,
@@ -31,6 +31,14 @@ const npmJob = job("npm", {
       name: "Build release",
       run: "cargo build --target wasm32-unknown-unknown --features wasm --release",
     },
+    {
+      name: "Build npm API",
+      run: [
+        "cargo build --target wasm32-unknown-unknown --features js --release --target-dir target/js",
+        "cargo install wasm-bindgen-cli --version 0.2.100 --locked",
+        "wasm-bindgen --target nodejs --out-dir deployment/npm/api --out-name api target/js/wasm32-unknown-unknown/release/dprint_plugin_dockerfile.wasm",
+      ],
+    },
     {
       uses: "actions/setup-node@v6",
       with: {
@@ -0,0 +1,64 @@
+// Benchmarks parsing, incremental reparsing and formatting of large generated
+// Dockerfiles. Run with `cargo bench`.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use dprint_plugin_dockerfile::TextEdit;
+use dprint_plugin_dockerfile::ast::Dockerfile;
+use dprint_plugin_dockerfile::configuration::ConfigurationBuilder;
+use dprint_plugin_dockerfile::format_dockerfile;
+
+/// Generates a multi-stage Dockerfile with roughly `lines` lines that exercises
+/// multi-line ENV/LABEL, HEALTHCHECK, comments and continued RUN commands.
+fn generate_dockerfile(lines: usize) -> String {
+  let mut text = String::new();
+  let mut stage = 0;
+  while text.lines().count() < lines {
+    text.push_str(&format!("# stage {stage}\nFROM    alpine:3.{stage} AS stage{stage}\n"));
+    text.push_str("ENV A=1 \\\n    B=2 \\\n    C=\"three four\"\n");
+    text.push_str("LABEL org.opencontainers.image.title=example \\\n      version=1.0\n");
+    text.push_str("RUN    apk add --no-cache curl \\\n    &&   rm -rf /var/cache/apk/*\n");
+    text.push_str("COPY --from=builder   /src /dest\n");
+    text.push_str("HEALTHCHECK --interval=30s --timeout=3s \\\n  CMD curl -f http://localhost/ || exit 1\n");
+    text.push_str("CMD [\"sh\", \"-c\", \"echo hi\"]\n\n");
+    stage += 1;
+  }
+  text
+}
+
+fn time<T>(iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+  let start = Instant::now();
+  for _ in 0..iterations {
+    std::hint::black_box(f());
+  }
+  start.elapsed() / iterations
+}
+
+fn main() {
+  let config = ConfigurationBuilder::new().build();
+  println!("{:>8} {:>12} {:>12} {:>12} {:>12}", "lines", "parse", "reparse", "format", "format/line");
+  let mut per_line = Vec::new();
+  for lines in [1_000, 10_000, 50_000] {
+    let text = generate_dockerfile(lines);
+    let file = Dockerfile::parse(&text).unwrap();
+    // a one character edit in the middle of the file
+    let middle = text[..text.len() / 2].rfind("apk add").unwrap();
+    let edit = TextEdit {
+      range: middle..middle + 1,
+      new_text: "A".to_string(),
+    };
+
+    let parse = time(5, || Dockerfile::parse(&text).unwrap());
+    let reparse = time(5, || file.reparse(&edit).unwrap());
+    let format = time(3, || format_dockerfile(&file, &config));
+    let format_per_line = format / lines as u32;
+    per_line.push(format_per_line);
+    println!("{lines:>8} {parse:>12.2?} {reparse:>12.2?} {format:>12.2?} {format_per_line:>12.2?}");
+  }
+
+  // formatting is linear when the cost per line stays roughly constant as the
+  // input grows (a quadratic line lookup would grow it 50x from 1k to 50k lines)
+  let growth = per_line.last().unwrap().as_secs_f64() / per_line.first().unwrap().as_secs_f64();
+  println!("format cost per line grew {growth:.2}x from 1k to 50k lines");
+}
@@ -1,2 +1,196 @@
 /** Gets an absolute path to the Wasm module. */
 export function getPath(): string;
+
+/**
+ * Formats the text of a Dockerfile, returning it unchanged when it's already formatted.
+ * @throws When the text can't be parsed or the configuration is invalid.
+ */
+export function format(text: string, config?: Configuration): string;
+
+/**
+ * Parses the text of a Dockerfile. The AST is described by ast.schema.json.
+ * @throws When the text can't be parsed.
+ */
+export function parse(text: string): Dockerfile;
+
+/**
+ * Gets the problems that formatting leaves in place in a Dockerfile, in the order of their spans.
+ * @throws When the text can't be parsed or the configuration is invalid.
+ */
+export function diagnostics(text: string, config?: Configuration): Diagnostic[];
+
+/** The plugin's configuration, as described by schema.json. */
+export interface Configuration {
+  lineWidth?: number;
+  newLineKind?: "auto" | "crlf" | "lf" | "system";
+  healthcheckCmdNewLine?: boolean;
+  copyDestinationNewLine?: boolean;
+  blankLineBetweenStages?: boolean;
+  maxConsecutiveBlankLines?: number;
+  ignoreNodeCommentText?: string;
+  ignoreFileCommentText?: string;
+  verifySemantics?: boolean;
+  shellFormatting?: boolean;
+  cmdForm?: CommandForm;
+  entrypointForm?: CommandForm;
+  quoteStyle?: "maintain" | "preferDouble" | "alwaysDouble" | "minimal";
+  keyValueLayout?: "maintain" | "fitOrBreakAll" | "onePerLine";
+  sortFlags?: boolean;
+  normalizeHealthcheckOptions?: boolean;
+}
+
+export type CommandForm = "maintain" | "exec";
+
+export interface Diagnostic {
+  span: Span;
+  message: string;
+}
+
+/** Byte offsets into the UTF-8 encoded text, with an exclusive end. */
+export interface Span {
+  start: number;
+  end: number;
+}
+
+export interface SpannedString {
+  span: Span;
+  content: string;
+}
+
+export interface Dockerfile {
+  /** The text the Dockerfile was parsed from. */
+  content: string;
+  /** The parser directives (ex. `# syntax=docker/dockerfile:1`) at the top of the file. */
+  directives: ParserDirective[];
+  instructions: Instruction[];
+  /** The line-continuation and escape character, from an escape directive. */
+  escape: "\\" | "`";
+}
+
+export interface ParserDirective {
+  span: Span;
+  name: SpannedString;
+  value: SpannedString;
+}
+
+/** A JSON array of strings, as in exec-form commands. The element contents are unquoted. */
+export interface StringArray {
+  span: Span;
+  elements: SpannedString[];
+}
+
+/** Text that can continue over several lines, with the comments between its lines. */
+export interface BreakableString {
+  span: Span;
+  components: BreakableStringComponent[];
+}
+
+export interface BreakableStringComponent {
+  kind: "string" | "comment";
+  span: Span;
+  content: string;
+}
+
+export type ShellOrExecExpr =
+  | ({ kind: "shell" } & BreakableString)
+  | ({ kind: "exec" } & StringArray);
+
+/** A name and value, such as a `--name=value` flag or a `LABEL` pair. */
+export interface NameValue {
+  span: Span;
+  name: SpannedString;
+  value: SpannedString;
+}
+
+export type Instruction =
+  | FromInstruction
+  | ArgInstruction
+  | LabelInstruction
+  | CommandInstruction
+  | CopyInstruction
+  | EnvInstruction
+  | OnbuildInstruction
+  | HealthcheckInstruction
+  | HeredocInstruction
+  | MiscInstruction
+  | UnknownInstruction;
+
+export interface FromInstruction {
+  kind: "from";
+  span: Span;
+  flags: NameValue[];
+  image: SpannedString;
+  alias: SpannedString | null;
+}
+
+export interface ArgInstruction {
+  kind: "arg";
+  span: Span;
+  name: SpannedString;
+  value: SpannedString | null;
+}
+
+export interface LabelInstruction {
+  kind: "label";
+  span: Span;
+  labels: NameValue[];
+}
+
+export interface CommandInstruction {
+  kind: "run" | "entrypoint" | "cmd" | "shell";
+  span: Span;
+  expr: ShellOrExecExpr;
+}
+
+/** A `COPY` instruction, or an `ADD`, which takes the same arguments. */
+export interface CopyInstruction {
+  kind: "copy" | "add";
+  span: Span;
+  flags: NameValue[];
+  args:
+    | { kind: "paths"; sources: SpannedString[]; destination: SpannedString }
+    | ({ kind: "exec" } & StringArray);
+}
+
+export interface EnvInstruction {
+  kind: "env";
+  span: Span;
+  vars: { span: Span; key: SpannedString; value: BreakableString }[];
+}
+
+export interface OnbuildInstruction {
+  kind: "onbuild";
+  span: Span;
+  instruction: Instruction;
+}
+
+export interface HealthcheckInstruction {
+  kind: "healthcheck";
+  span: Span;
+  flags: NameValue[];
+  /** The nested `CMD` instruction, or `null` for `HEALTHCHECK NONE`. */
+  cmd: Instruction | null;
+}
+
+export interface HeredocInstruction {
+  kind: "heredoc";
+  span: Span;
+  /** The instruction on the line the heredocs start on. */
+  instruction: Instruction;
+  /** The verbatim text of the heredoc bodies and their closing delimiters. */
+  body: string;
+}
+
+export interface MiscInstruction {
+  kind: "misc";
+  span: Span;
+  instruction: SpannedString;
+  arguments: BreakableString;
+}
+
+/** A line that couldn't be parsed, kept as written. */
+export interface UnknownInstruction {
+  kind: "unknown";
+  span: Span;
+  content: string;
+}
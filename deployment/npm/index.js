@@ -6,6 +6,46 @@ function getPath() {
   return require("path").join(__dirname, "plugin.wasm");
 }
 
+/**
+ * Loads the wasm-bindgen module on first use, so that using only `getPath`
+ * doesn't instantiate it.
+ */
+function api() {
+  return require("./api/api.js");
+}
+
+/**
+ * Formats the text of a Dockerfile.
+ * @param {string} text
+ * @param {object} [config]
+ * @returns {string}
+ */
+function format(text, config) {
+  return api().format(text, JSON.stringify(config ?? {}));
+}
+
+/**
+ * Parses the text of a Dockerfile.
+ * @param {string} text
+ * @returns {object}
+ */
+function parse(text) {
+  return JSON.parse(api().parse(text));
+}
+
+/**
+ * Gets the problems that formatting leaves in place in a Dockerfile.
+ * @param {string} text
+ * @param {object} [config]
+ * @returns {object[]}
+ */
+function diagnostics(text, config) {
+  return JSON.parse(api().diagnostics(text, JSON.stringify(config ?? {})));
+}
+
 module.exports = {
   getPath,
+  format,
+  parse,
+  diagnostics,
 };
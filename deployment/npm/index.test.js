@@ -1,7 +1,7 @@
 // @ts-check
 const assert = require("assert");
 const createFromBuffer = require("@dprint/formatter").createFromBuffer;
-const getPath = require("./index").getPath;
+const { getPath, format, parse, diagnostics } = require("./index");
 
 const buffer = require("fs").readFileSync(getPath());
 const formatter = createFromBuffer(buffer);
@@ -11,3 +11,25 @@ const result = formatter.formatText({
 });
 
 assert.strictEqual(result, "RUN /bin/bash\n");
+
+// format
+assert.strictEqual(format("from    alpine"), "FROM alpine\n");
+assert.strictEqual(format("FROM alpine\n", { newLineKind: "crlf" }), "FROM alpine\r\n");
+assert.throws(() => format("FROM alpine\n", { unknownOption: true }), /unknownOption/);
+
+// parse
+const file = parse("FROM alpine AS build\nRUN make\n");
+assert.strictEqual(file.escape, "\\");
+assert.deepStrictEqual(file.instructions.map((instruction) => instruction.kind), ["from", "run"]);
+const from = file.instructions[0];
+assert.ok(from.kind === "from");
+assert.strictEqual(from.image.content, "alpine");
+assert.deepStrictEqual(from.alias?.span, { start: 15, end: 20 });
+
+// diagnostics
+assert.deepStrictEqual(diagnostics("ONBUILD FROM alpine\n"), [{
+  span: { start: 0, end: 19 },
+  message: "FROM isn't allowed as an ONBUILD trigger",
+}]);
+assert.strictEqual(diagnostics("CMD echo $HOME", { cmdForm: "exec" }).length, 1);
+assert.deepStrictEqual(diagnostics("FROM alpine\n"), []);
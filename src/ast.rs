@@ -3,8 +3,11 @@
 // These mirror the subset of the `dockerfile-parser` crate's public types that
 // the formatter relies on. They are produced by [`crate::parser`].
 
+use crate::TextEdit;
+
 /// A byte-index range into the original Dockerfile text.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
   pub start: usize,
   pub end: usize,
@@ -17,21 +20,17 @@ impl Span {
 
   /// Determines the 0-indexed line number and line-relative span of this span.
   pub fn relative_span(&self, dockerfile: &Dockerfile) -> (usize, Span) {
-    let mut line_start_offset = 0;
-    let mut lines = 0;
-    for (i, c) in dockerfile.content.as_bytes().iter().enumerate() {
-      if i == self.start {
-        break;
-      }
-      if *c == b'\n' {
-        lines += 1;
-        line_start_offset = i + 1;
-      }
-    }
-
-    let start = self.start - line_start_offset;
+    let (line, start) = dockerfile.line_col(self.start);
     let end = start + (self.end - self.start);
-    (lines, Span { start, end })
+    (line, Span { start, end })
+  }
+
+  /// Moves the span by `delta` bytes.
+  pub(crate) fn shifted(self, delta: isize) -> Span {
+    Span {
+      start: self.start.wrapping_add_signed(delta),
+      end: self.end.wrapping_add_signed(delta),
+    }
   }
 }
 
@@ -43,14 +42,21 @@ impl From<(usize, usize)> for Span {
 
 /// A parsed Dockerfile.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "DockerfileData"))]
 pub struct Dockerfile {
   /// The raw content of the Dockerfile.
   pub content: String,
+  /// The parser directives (e.g. `# syntax=docker/dockerfile:1`) at the top of
+  /// the file.
+  pub directives: Vec<ParserDirective>,
   /// An ordered list of all parsed instructions.
   pub instructions: Vec<Instruction>,
   /// The line-continuation / escape character, from a `# escape=` directive
   /// (`\` by default).
   pub escape: char,
+  /// The byte offset at which each line of `content` starts.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub(crate) line_starts: Vec<usize>,
 }
 
 impl Dockerfile {
@@ -58,10 +64,79 @@ impl Dockerfile {
   pub fn parse(input: &str) -> Result<Dockerfile, monch::ParseErrorFailureError> {
     crate::parser::parse(input)
   }
+
+  /// Parses the Dockerfile that results from applying `edit` to this one's
+  /// content, reparsing only the instructions the edit touches and reusing the
+  /// rest.
+  pub fn reparse(&self, edit: &TextEdit) -> Result<Dockerfile, monch::ParseErrorFailureError> {
+    crate::parser::reparse(self, edit)
+  }
+
+  /// The 0-indexed line and byte column of the byte `offset` in the content.
+  pub fn line_col(&self, offset: usize) -> (usize, usize) {
+    let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+    (line, offset - self.line_starts[line])
+  }
+
+  /// The byte offset in the content of a 0-indexed line and byte column, or
+  /// `None` if the position is past the end of the line or file.
+  pub fn offset(&self, line: usize, col: usize) -> Option<usize> {
+    let start = *self.line_starts.get(line)?;
+    let end = match self.line_starts.get(line + 1) {
+      Some(next_start) => next_start - 1,
+      None => self.content.len(),
+    };
+    let offset = start + col;
+    if offset <= end { Some(offset) } else { None }
+  }
+}
+
+/// Finds the byte offset at which each line of `text` starts.
+pub(crate) fn line_starts(text: &str) -> Vec<usize> {
+  std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// The serialized fields of a [`Dockerfile`], which leave out the line starts
+/// that can be computed from its content.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DockerfileData {
+  content: String,
+  directives: Vec<ParserDirective>,
+  instructions: Vec<Instruction>,
+  escape: char,
+}
+
+#[cfg(feature = "serde")]
+impl From<DockerfileData> for Dockerfile {
+  fn from(data: DockerfileData) -> Self {
+    Dockerfile {
+      line_starts: line_starts(&data.content),
+      content: data.content,
+      directives: data.directives,
+      instructions: data.instructions,
+      escape: data.escape,
+    }
+  }
+}
+
+/// A [parser directive][directive] such as `# syntax=docker/dockerfile:1`,
+/// `` # escape=` `` or `# check=skip=JSONArgsRecommended`.
+///
+/// The name's content is lowercased; the value's content is trimmed.
+///
+/// [directive]: https://docs.docker.com/reference/dockerfile/#parser-directives
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParserDirective {
+  pub span: Span,
+  pub name: SpannedString,
+  pub value: SpannedString,
 }
 
 /// A single Dockerfile instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(tag = "kind", rename_all = "camelCase"))]
 pub enum Instruction {
   From(FromInstruction),
   Arg(ArgInstruction),
@@ -70,6 +145,7 @@ pub enum Instruction {
   Entrypoint(EntrypointInstruction),
   Cmd(CmdInstruction),
   Copy(CopyInstruction),
+  Add(AddInstruction),
   Env(EnvInstruction),
   Shell(ShellInstruction),
   Onbuild(OnbuildInstruction),
@@ -91,6 +167,7 @@ impl Instruction {
       Instruction::Entrypoint(i) => i.span,
       Instruction::Cmd(i) => i.span,
       Instruction::Copy(i) => i.span,
+      Instruction::Add(i) => i.span,
       Instruction::Env(i) => i.span,
       Instruction::Shell(i) => i.span,
       Instruction::Onbuild(i) => i.span,
@@ -104,6 +181,7 @@ impl Instruction {
 
 /// A string with a character span.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpannedString {
   pub span: Span,
   pub content: String,
@@ -111,6 +189,7 @@ pub struct SpannedString {
 
 /// A comment with a character span.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpannedComment {
   pub span: Span,
   pub content: String,
@@ -118,6 +197,7 @@ pub struct SpannedComment {
 
 /// A string array (ex. `["executable", "param1", "param2"]`).
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringArray {
   pub span: Span,
   pub elements: Vec<SpannedString>,
@@ -125,6 +205,7 @@ pub struct StringArray {
 
 /// A component of a breakable string.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(tag = "kind", rename_all = "camelCase"))]
 pub enum BreakableStringComponent {
   String(SpannedString),
   Comment(SpannedComment),
@@ -133,6 +214,7 @@ pub enum BreakableStringComponent {
 /// A Docker string that may be broken across several lines, separated by line
 /// continuations (`\\\n`), and possibly intermixed with comments.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BreakableString {
   pub span: Span,
   pub components: Vec<BreakableStringComponent>,
@@ -140,6 +222,7 @@ pub struct BreakableString {
 
 /// A string that is either in shell form or exec form (`["a", "b"]`).
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(tag = "kind", rename_all = "camelCase"))]
 pub enum ShellOrExecExpr {
   Shell(BreakableString),
   Exec(StringArray),
@@ -147,6 +230,7 @@ pub enum ShellOrExecExpr {
 
 /// A Dockerfile `FROM` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FromInstruction {
   pub span: Span,
   pub flags: Vec<FromFlag>,
@@ -156,6 +240,7 @@ pub struct FromInstruction {
 
 /// A key/value pair passed to a `FROM` instruction as a flag.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FromFlag {
   pub span: Span,
   pub name: SpannedString,
@@ -164,6 +249,7 @@ pub struct FromFlag {
 
 /// A Dockerfile `ARG` instruction.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArgInstruction {
   pub span: Span,
   pub name: SpannedString,
@@ -172,6 +258,7 @@ pub struct ArgInstruction {
 
 /// A Dockerfile `LABEL` instruction. A single instruction may set many labels.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LabelInstruction {
   pub span: Span,
   pub labels: Vec<Label>,
@@ -179,6 +266,7 @@ pub struct LabelInstruction {
 
 /// A single label key/value pair.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Label {
   pub span: Span,
   pub name: SpannedString,
@@ -187,6 +275,7 @@ pub struct Label {
 
 /// A Dockerfile `RUN` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RunInstruction {
   pub span: Span,
   pub expr: ShellOrExecExpr,
@@ -194,6 +283,7 @@ pub struct RunInstruction {
 
 /// A Dockerfile `ENTRYPOINT` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EntrypointInstruction {
   pub span: Span,
   pub expr: ShellOrExecExpr,
@@ -201,6 +291,7 @@ pub struct EntrypointInstruction {
 
 /// A Dockerfile `CMD` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CmdInstruction {
   pub span: Span,
   pub expr: ShellOrExecExpr,
@@ -208,22 +299,35 @@ pub struct CmdInstruction {
 
 /// A Dockerfile `COPY` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyInstruction {
   pub span: Span,
   pub flags: Vec<CopyFlag>,
   pub args: CopyArgs,
 }
 
-/// The argument portion of a `COPY` instruction: either space-separated paths
-/// or the JSON/exec array form (`COPY ["src", "dest"]`).
+/// A Dockerfile `ADD` instruction, which takes its arguments in the same forms
+/// as `COPY`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddInstruction {
+  pub span: Span,
+  pub flags: Vec<CopyFlag>,
+  pub args: CopyArgs,
+}
+
+/// The argument portion of a `COPY` or `ADD` instruction: either
+/// space-separated paths or the JSON/exec array form (`COPY ["src", "dest"]`).
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(tag = "kind", rename_all = "camelCase"))]
 pub enum CopyArgs {
   Paths { sources: Vec<SpannedString>, destination: SpannedString },
   Exec(StringArray),
 }
 
-/// A key/value pair passed to a `COPY` instruction as a flag.
+/// A key/value pair passed to a `COPY` or `ADD` instruction as a flag.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyFlag {
   pub span: Span,
   pub name: SpannedString,
@@ -232,6 +336,7 @@ pub struct CopyFlag {
 
 /// A Dockerfile `ENV` instruction.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvInstruction {
   pub span: Span,
   pub vars: Vec<EnvVar>,
@@ -239,6 +344,7 @@ pub struct EnvInstruction {
 
 /// An environment variable key/value pair.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvVar {
   pub span: Span,
   pub key: SpannedString,
@@ -248,6 +354,7 @@ pub struct EnvVar {
 /// A Dockerfile `SHELL` instruction. Docker only permits the exec (JSON array)
 /// form, but the shell form is tolerated so malformed input still round-trips.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShellInstruction {
   pub span: Span,
   pub expr: ShellOrExecExpr,
@@ -255,6 +362,7 @@ pub struct ShellInstruction {
 
 /// A Dockerfile `ONBUILD` instruction, wrapping the instruction it triggers.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OnbuildInstruction {
   pub span: Span,
   pub instruction: Box<Instruction>,
@@ -265,6 +373,7 @@ pub struct OnbuildInstruction {
 /// Either `HEALTHCHECK [OPTIONS] CMD <command>` (with `cmd` set to the nested
 /// `CMD` instruction) or `HEALTHCHECK NONE` (with `cmd` being `None`).
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HealthcheckInstruction {
   pub span: Span,
   pub flags: Vec<HealthcheckFlag>,
@@ -274,6 +383,7 @@ pub struct HealthcheckInstruction {
 /// A key/value option passed to a `HEALTHCHECK` instruction, e.g.
 /// `--interval=30s`.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HealthcheckFlag {
   pub span: Span,
   pub name: SpannedString,
@@ -294,6 +404,7 @@ pub struct HealthcheckFlag {
 ///
 /// [heredoc]: https://docs.docker.com/engine/reference/builder/#here-documents
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HeredocInstruction {
   pub span: Span,
   pub instruction: Box<Instruction>,
@@ -305,8 +416,215 @@ pub struct HeredocInstruction {
 /// Includes valid-but-unparsed commands such as `EXPOSE`, `VOLUME`, `USER`,
 /// `WORKDIR`, `ONBUILD`, `STOPSIGNAL`, `HEALTHCHECK`, `SHELL`, `MAINTAINER`.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MiscInstruction {
   pub span: Span,
   pub instruction: SpannedString,
   pub arguments: BreakableString,
 }
+
+/// Moves every span within a node by a byte delta, so nodes after an edit can
+/// be reused when reparsing.
+pub(crate) trait ShiftSpans {
+  fn shift_spans(&mut self, delta: isize);
+}
+
+macro_rules! impl_shift_spans {
+  ($($name:ident { $($field:ident),* },)*) => {
+    $(
+      impl ShiftSpans for $name {
+        fn shift_spans(&mut self, delta: isize) {
+          $(self.$field.shift_spans(delta);)*
+        }
+      }
+    )*
+  };
+}
+
+impl_shift_spans!(
+  SpannedString { span },
+  SpannedComment { span },
+  StringArray { span, elements },
+  BreakableString { span, components },
+  FromInstruction { span, flags, image, alias },
+  FromFlag { span, name, value },
+  ArgInstruction { span, name, value },
+  LabelInstruction { span, labels },
+  Label { span, name, value },
+  RunInstruction { span, expr },
+  EntrypointInstruction { span, expr },
+  CmdInstruction { span, expr },
+  CopyInstruction { span, flags, args },
+  AddInstruction { span, flags, args },
+  CopyFlag { span, name, value },
+  EnvInstruction { span, vars },
+  EnvVar { span, key, value },
+  ShellInstruction { span, expr },
+  OnbuildInstruction { span, instruction },
+  HealthcheckInstruction { span, flags, cmd },
+  HealthcheckFlag { span, name, value },
+  HeredocInstruction { span, instruction },
+  MiscInstruction { span, instruction, arguments },
+);
+
+impl ShiftSpans for Span {
+  fn shift_spans(&mut self, delta: isize) {
+    *self = self.shifted(delta);
+  }
+}
+
+impl<T: ShiftSpans> ShiftSpans for Vec<T> {
+  fn shift_spans(&mut self, delta: isize) {
+    for item in self {
+      item.shift_spans(delta);
+    }
+  }
+}
+
+impl<T: ShiftSpans> ShiftSpans for Option<T> {
+  fn shift_spans(&mut self, delta: isize) {
+    if let Some(item) = self {
+      item.shift_spans(delta);
+    }
+  }
+}
+
+impl<T: ShiftSpans> ShiftSpans for Box<T> {
+  fn shift_spans(&mut self, delta: isize) {
+    (**self).shift_spans(delta);
+  }
+}
+
+impl ShiftSpans for BreakableStringComponent {
+  fn shift_spans(&mut self, delta: isize) {
+    match self {
+      BreakableStringComponent::String(s) => s.shift_spans(delta),
+      BreakableStringComponent::Comment(c) => c.shift_spans(delta),
+    }
+  }
+}
+
+impl ShiftSpans for ShellOrExecExpr {
+  fn shift_spans(&mut self, delta: isize) {
+    match self {
+      ShellOrExecExpr::Shell(s) => s.shift_spans(delta),
+      ShellOrExecExpr::Exec(e) => e.shift_spans(delta),
+    }
+  }
+}
+
+impl ShiftSpans for CopyArgs {
+  fn shift_spans(&mut self, delta: isize) {
+    match self {
+      CopyArgs::Paths { sources, destination } => {
+        sources.shift_spans(delta);
+        destination.shift_spans(delta);
+      }
+      CopyArgs::Exec(array) => array.shift_spans(delta),
+    }
+  }
+}
+
+impl ShiftSpans for Instruction {
+  fn shift_spans(&mut self, delta: isize) {
+    match self {
+      Instruction::From(i) => i.shift_spans(delta),
+      Instruction::Arg(i) => i.shift_spans(delta),
+      Instruction::Label(i) => i.shift_spans(delta),
+      Instruction::Run(i) => i.shift_spans(delta),
+      Instruction::Entrypoint(i) => i.shift_spans(delta),
+      Instruction::Cmd(i) => i.shift_spans(delta),
+      Instruction::Copy(i) => i.shift_spans(delta),
+      Instruction::Add(i) => i.shift_spans(delta),
+      Instruction::Env(i) => i.shift_spans(delta),
+      Instruction::Shell(i) => i.shift_spans(delta),
+      Instruction::Onbuild(i) => i.shift_spans(delta),
+      Instruction::Healthcheck(i) => i.shift_spans(delta),
+      Instruction::Heredoc(i) => i.shift_spans(delta),
+      Instruction::Misc(i) => i.shift_spans(delta),
+      Instruction::Unknown(i) => i.shift_spans(delta),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn converts_between_offsets_and_line_columns() {
+    let file = Dockerfile::parse("FROM a\r\n\nRUN b\n").unwrap();
+    assert_eq!(file.line_col(0), (0, 0));
+    assert_eq!(file.line_col(7), (0, 7));
+    assert_eq!(file.line_col(8), (1, 0));
+    assert_eq!(file.line_col(13), (2, 4));
+    assert_eq!(file.line_col(15), (3, 0));
+    assert_eq!(file.offset(2, 4), Some(13));
+    assert_eq!(file.offset(1, 0), Some(8));
+    assert_eq!(file.offset(1, 1), None);
+    assert_eq!(file.offset(3, 0), Some(15));
+    assert_eq!(file.offset(4, 0), None);
+    for offset in 0..=file.content.len() {
+      let (line, col) = file.line_col(offset);
+      assert_eq!(file.offset(line, col), Some(offset));
+    }
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn round_trips_through_json() {
+    let file = Dockerfile::parse("# escape=`\nFROM --platform=$P a AS b\nRUN c `\n  # d\n  e\nHEALTHCHECK NONE\n").unwrap();
+    let json = serde_json::to_value(&file).unwrap();
+    assert_eq!(
+      json["instructions"][1],
+      serde_json::json!({
+        "kind": "run",
+        "span": { "start": 37, "end": 54 },
+        "expr": {
+          "kind": "shell",
+          "span": { "start": 41, "end": 54 },
+          "components": [
+            { "kind": "string", "span": { "start": 41, "end": 43 }, "content": "c " },
+            { "kind": "comment", "span": { "start": 47, "end": 50 }, "content": "# d" },
+            { "kind": "string", "span": { "start": 51, "end": 54 }, "content": "  e" },
+          ],
+        },
+      })
+    );
+    assert_eq!(json["escape"], "`");
+    let deserialized = serde_json::from_value::<Dockerfile>(json).unwrap();
+    assert_eq!(deserialized, file);
+    assert_eq!(deserialized.line_col(41), (2, 4));
+  }
+
+  /// Keeps `deployment/ast.schema.json` in sync with the instruction kinds.
+  #[cfg(feature = "serde")]
+  #[test]
+  fn schema_lists_every_instruction_kind() {
+    let schema = serde_json::from_str::<serde_json::Value>(include_str!("../deployment/ast.schema.json")).unwrap();
+    let mut schema_kinds = schema["definitions"]["instruction"]["oneOf"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .flat_map(|variant| {
+        let kind = &variant["properties"]["kind"];
+        match kind["enum"].as_array() {
+          Some(kinds) => kinds.iter().map(|kind| kind.as_str().unwrap().to_string()).collect(),
+          None => vec![kind["const"].as_str().unwrap().to_string()],
+        }
+      })
+      .collect::<Vec<_>>();
+    let file = Dockerfile::parse(
+      "FROM a\nARG b\nLABEL c=d\nRUN e\nENTRYPOINT f\nCMD g\nCOPY h i\nENV j=k\nSHELL [\"l\"]\nONBUILD RUN m\nHEALTHCHECK NONE\nRUN <<EOF\nn\nEOF\nEXPOSE 80\nFROM\n",
+    )
+    .unwrap();
+    let mut kinds = file
+      .instructions
+      .iter()
+      .map(|instruction| serde_json::to_value(instruction).unwrap()["kind"].as_str().unwrap().to_string())
+      .collect::<Vec<_>>();
+    schema_kinds.sort();
+    kinds.sort();
+    assert_eq!(kinds, schema_kinds);
+  }
+}
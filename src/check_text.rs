@@ -0,0 +1,170 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use similar::Algorithm;
+use similar::DiffOp;
+
+use crate::configuration::Configuration;
+use crate::error::FormatError;
+use crate::format_text::format_text;
+
+/// A run of changed lines between a file and its formatted text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+  /// The 0-indexed lines of the original text that are replaced. Empty when
+  /// lines are only inserted, in which case `start` is the line they go before.
+  pub old_lines: Range<usize>,
+  /// The 0-indexed lines of the formatted text that replace them. Empty when
+  /// lines are only removed.
+  pub new_lines: Range<usize>,
+  /// The original text of `old_lines`, including line endings.
+  pub old_text: String,
+  /// The formatted text of `new_lines`, including line endings.
+  pub new_text: String,
+}
+
+/// Renders the hunk in unified diff format without context lines.
+impl fmt::Display for DiffHunk {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "@@ -{} +{} @@", unified_range(&self.old_lines), unified_range(&self.new_lines))?;
+    write_lines(f, '-', &self.old_text)?;
+    write_lines(f, '+', &self.new_text)
+  }
+}
+
+/// Formats the text and returns the hunks that differ from the formatted
+/// output, or an empty list if it's already formatted.
+///
+/// Lines are compared along with their line endings. The output keeps the
+/// line endings selected by `newLineKind` (by default those of the input), so
+/// a change in a CRLF file only produces a hunk for the lines that changed.
+///
+/// This needs the `diff` feature, which keeps the diff library out of the
+/// Wasm plugin.
+pub fn check_text(file_path: &Path, text: &str, config: &Configuration) -> Result<Vec<DiffHunk>, FormatError> {
+  Ok(match format_text(file_path, text, config)? {
+    Some(formatted) => diff_lines(text, &formatted),
+    None => Vec::new(),
+  })
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+  let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+  let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+  let mut hunks: Vec<DiffHunk> = Vec::new();
+  for op in similar::capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines) {
+    if matches!(op, DiffOp::Equal { .. }) {
+      continue;
+    }
+    let (old_range, new_range) = (op.old_range(), op.new_range());
+    match hunks.last_mut() {
+      // a replacement can come as a deletion followed by an insertion
+      Some(hunk) if hunk.old_lines.end == old_range.start && hunk.new_lines.end == new_range.start => {
+        hunk.old_lines.end = old_range.end;
+        hunk.new_lines.end = new_range.end;
+      }
+      _ => hunks.push(DiffHunk {
+        old_lines: old_range,
+        new_lines: new_range,
+        old_text: String::new(),
+        new_text: String::new(),
+      }),
+    }
+  }
+  for hunk in &mut hunks {
+    hunk.old_text = old_lines[hunk.old_lines.clone()].concat();
+    hunk.new_text = new_lines[hunk.new_lines.clone()].concat();
+  }
+  hunks
+}
+
+/// A line range as `start,count` with a 1-indexed start, or the line before
+/// the position when the range is empty.
+fn unified_range(lines: &Range<usize>) -> String {
+  if lines.is_empty() {
+    format!("{},0", lines.start)
+  } else {
+    format!("{},{}", lines.start + 1, lines.len())
+  }
+}
+
+fn write_lines(f: &mut fmt::Formatter<'_>, prefix: char, text: &str) -> fmt::Result {
+  for line in text.split_inclusive('\n') {
+    write!(f, "{}{}", prefix, line)?;
+    if !line.ends_with('\n') {
+      writeln!(f, "\n\\ No newline at end of file")?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use dprint_core::configuration::NewLineKind;
+
+  use super::*;
+  use crate::configuration::ConfigurationBuilder;
+
+  fn check(text: &str, config: &Configuration) -> Vec<DiffHunk> {
+    check_text(Path::new("Dockerfile"), text, config).unwrap()
+  }
+
+  #[test]
+  fn returns_changed_lines() {
+    let config = ConfigurationBuilder::new().build();
+    assert_eq!(check("FROM alpine\nRUN echo\n", &config), []);
+    assert_eq!(
+      check("FROM    alpine\nRUN echo\nCMD    a\n", &config),
+      [
+        DiffHunk {
+          old_lines: 0..1,
+          new_lines: 0..1,
+          old_text: "FROM    alpine\n".to_string(),
+          new_text: "FROM alpine\n".to_string(),
+        },
+        DiffHunk {
+          old_lines: 2..3,
+          new_lines: 2..3,
+          old_text: "CMD    a\n".to_string(),
+          new_text: "CMD a\n".to_string(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn returns_removed_and_inserted_lines() {
+    let config = ConfigurationBuilder::new().build();
+    let hunks = check("FROM alpine\n\n\n\nRUN a \\\n  && b\n", &config);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!((hunks[0].old_lines.clone(), hunks[0].new_lines.clone()), (2..4, 2..2));
+    assert_eq!(hunks[0].to_string(), "@@ -3,2 +2,0 @@\n-\n-\n");
+
+    let hunks = check("FROM alpine", &config);
+    assert_eq!(
+      hunks[0].to_string(),
+      "@@ -1,1 +1,1 @@\n-FROM alpine\n\\ No newline at end of file\n+FROM alpine\n"
+    );
+  }
+
+  #[test]
+  fn keeps_crlf_lines_unchanged() {
+    let text = "FROM alpine\r\nRUN    echo\r\nCMD a\r\n";
+    for new_line_kind in [NewLineKind::Auto, NewLineKind::CarriageReturnLineFeed] {
+      let config = ConfigurationBuilder::new().new_line_kind(new_line_kind).build();
+      assert_eq!(
+        check(text, &config),
+        [DiffHunk {
+          old_lines: 1..2,
+          new_lines: 1..2,
+          old_text: "RUN    echo\r\n".to_string(),
+          new_text: "RUN echo\r\n".to_string(),
+        }]
+      );
+    }
+    // converting the line endings changes every line
+    let config = ConfigurationBuilder::new().new_line_kind(NewLineKind::LineFeed).build();
+    assert_eq!(check(text, &config)[0].old_lines, 0..3);
+  }
+}
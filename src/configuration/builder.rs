@@ -71,6 +71,104 @@ impl ConfigurationBuilder {
     self.insert("healthcheckCmdNewLine", value.into())
   }
 
+  /// Whether to always put the destination of a `COPY` or `ADD` instruction on
+  /// its own continuation line, even if it would fit on one line.
+  /// Default: `false`
+  pub fn copy_destination_new_line(&mut self, value: bool) -> &mut Self {
+    self.insert("copyDestinationNewLine", value.into())
+  }
+
+  /// Whether to put exactly one blank line before every `FROM` except the
+  /// first, keeping a comment block directly above the `FROM` with it.
+  /// Default: `false`
+  pub fn blank_line_between_stages(&mut self, value: bool) -> &mut Self {
+    self.insert("blankLineBetweenStages", value.into())
+  }
+
+  /// The maximum number of consecutive blank lines to keep between
+  /// instructions. Longer runs are collapsed.
+  /// Default: `1`
+  pub fn max_consecutive_blank_lines(&mut self, value: u32) -> &mut Self {
+    self.insert("maxConsecutiveBlankLines", (value as i32).into())
+  }
+
+  /// The exact file names the plugin formats.
+  /// Default: `["Dockerfile", "Containerfile"]`
+  pub fn file_names(&mut self, value: &[&str]) -> &mut Self {
+    self.insert("fileNames", ConfigKeyValue::Array(value.iter().map(|name| (*name).into()).collect()))
+  }
+
+  /// The `*.<extension>` patterns of files the plugin formats.
+  /// Default: `["*.dockerfile", "*.containerfile"]`
+  pub fn file_patterns(&mut self, value: &[&str]) -> &mut Self {
+    self.insert("filePatterns", ConfigKeyValue::Array(value.iter().map(|pattern| (*pattern).into()).collect()))
+  }
+
+  /// The text of a comment that makes the instruction after it print exactly
+  /// as written.
+  /// Default: `"dprint-ignore"`
+  pub fn ignore_node_comment_text(&mut self, value: &str) -> &mut Self {
+    self.insert("ignoreNodeCommentText", value.into())
+  }
+
+  /// The text of a comment at the top of a file that skips formatting the file.
+  /// Default: `"dprint-ignore-file"`
+  pub fn ignore_file_comment_text(&mut self, value: &str) -> &mut Self {
+    self.insert("ignoreFileCommentText", value.into())
+  }
+
+  /// Whether to re-parse the formatted text and fail instead of returning it
+  /// if the meaning of any instruction changed.
+  /// Default: `false`
+  pub fn verify_semantics(&mut self, value: bool) -> &mut Self {
+    self.insert("verifySemantics", value.into())
+  }
+
+  /// Whether to parse shell-form `RUN` commands and print them with
+  /// consistent spacing and indented blocks.
+  /// Default: `false`
+  pub fn shell_formatting(&mut self, value: bool) -> &mut Self {
+    self.insert("shellFormatting", value.into())
+  }
+
+  /// The form to print `CMD` instructions in.
+  /// Default: `CommandForm::Maintain`
+  pub fn cmd_form(&mut self, value: CommandForm) -> &mut Self {
+    self.insert("cmdForm", value.to_string().into())
+  }
+
+  /// The form to print `ENTRYPOINT` instructions in.
+  /// Default: `CommandForm::Maintain`
+  pub fn entrypoint_form(&mut self, value: CommandForm) -> &mut Self {
+    self.insert("entrypointForm", value.to_string().into())
+  }
+
+  /// How to quote the values of `ENV`, `LABEL` and `ARG` instructions.
+  /// Default: `QuoteStyle::Maintain`
+  pub fn quote_style(&mut self, value: QuoteStyle) -> &mut Self {
+    self.insert("quoteStyle", value.to_string().into())
+  }
+
+  /// How to lay out the pairs of `ENV` and `LABEL` instructions.
+  /// Default: `KeyValueLayout::Maintain`
+  pub fn key_value_layout(&mut self, value: KeyValueLayout) -> &mut Self {
+    self.insert("keyValueLayout", value.to_string().into())
+  }
+
+  /// Whether to put the flags of `FROM`, `COPY` and `HEALTHCHECK` instructions
+  /// in a canonical order.
+  /// Default: `false`
+  pub fn sort_flags(&mut self, value: bool) -> &mut Self {
+    self.insert("sortFlags", value.into())
+  }
+
+  /// Whether to write the durations and retries of `HEALTHCHECK` options in
+  /// their normal form.
+  /// Default: `false`
+  pub fn normalize_healthcheck_options(&mut self, value: bool) -> &mut Self {
+    self.insert("normalizeHealthcheckOptions", value.into())
+  }
+
   #[cfg(test)]
   pub(super) fn get_inner_config(&self) -> ConfigKeyMap {
     self.config.clone()
@@ -95,10 +193,25 @@ mod tests {
     config
       .new_line_kind(NewLineKind::CarriageReturnLineFeed)
       .line_width(90)
-      .healthcheck_cmd_new_line(true);
+      .healthcheck_cmd_new_line(true)
+      .copy_destination_new_line(true)
+      .blank_line_between_stages(true)
+      .max_consecutive_blank_lines(2)
+      .file_names(&["Dockerfile", "Dockerfile.dev"])
+      .file_patterns(&["*.Dockerfile"])
+      .ignore_node_comment_text("deno-fmt-ignore")
+      .ignore_file_comment_text("deno-fmt-ignore-file")
+      .verify_semantics(true)
+      .shell_formatting(true)
+      .cmd_form(CommandForm::Exec)
+      .entrypoint_form(CommandForm::Exec)
+      .quote_style(QuoteStyle::Minimal)
+      .key_value_layout(KeyValueLayout::OnePerLine)
+      .sort_flags(true)
+      .normalize_healthcheck_options(true);
 
     let inner_config = config.get_inner_config();
-    assert_eq!(inner_config.len(), 3);
+    assert_eq!(inner_config.len(), 18);
     let diagnostics = resolve_config(inner_config, &Default::default()).diagnostics;
     assert_eq!(diagnostics.len(), 0);
   }
@@ -116,6 +229,21 @@ mod tests {
     assert_eq!(config.new_line_kind, NewLineKind::CarriageReturnLineFeed);
   }
 
+  #[test]
+  fn resolves_file_matching() {
+    let config = ConfigurationBuilder::new().build();
+    assert_eq!(config.file_names, ["Dockerfile", "Containerfile"]);
+    assert_eq!(config.file_extensions(), ["dockerfile", "containerfile"]);
+
+    let mut config = ConfigurationBuilder::new();
+    config.file_patterns(&["*.Dockerfile", "Dockerfile.*"]);
+    let result = resolve_config(config.get_inner_config(), &Default::default());
+    assert_eq!(result.config.file_extensions(), ["Dockerfile"]);
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].property_name, "filePatterns");
+    assert!(result.diagnostics[0].message.contains("\"**/Dockerfile.*\"' entry in dprint's 'associations'"));
+  }
+
   #[test]
   fn use_defaults_when_global_not_set() {
     let global_config = Default::default();
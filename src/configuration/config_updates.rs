@@ -0,0 +1,164 @@
+use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::ConfigKeyValue;
+use dprint_core::plugins::CheckConfigUpdatesMessage;
+use dprint_core::plugins::ConfigChange;
+use dprint_core::plugins::ConfigChangeKind;
+
+/// A change to the configuration's keys or values made in a plugin version.
+pub enum ConfigMigration {
+  /// The key `from` was renamed to `to`.
+  Rename { from: &'static str, to: &'static str },
+  /// The value of `key` changed shape. `convert` returns the new value for an
+  /// old-shaped value, or `None` when the value is already current.
+  Convert {
+    key: &'static str,
+    convert: fn(&ConfigKeyValue) -> Option<ConfigKeyValue>,
+  },
+  /// The key was removed.
+  Remove { key: &'static str },
+}
+
+/// The migrations to apply to configuration written for an older version of
+/// the plugin, each paired with the version that made the change. Add an entry
+/// here when renaming, reshaping or removing an option.
+const CONFIG_MIGRATIONS: &[(&str, ConfigMigration)] = &[];
+
+/// Gets the changes that update configuration written for an older plugin
+/// version (`message.old_version`) to the current one.
+pub fn check_config_updates(message: &CheckConfigUpdatesMessage) -> Vec<ConfigChange> {
+  get_config_changes(CONFIG_MIGRATIONS, message.old_version.as_deref(), &message.config)
+}
+
+fn get_config_changes(migrations: &[(&str, ConfigMigration)], old_version: Option<&str>, config: &ConfigKeyMap) -> Vec<ConfigChange> {
+  let mut changes = Vec::new();
+  // dprint versions before 0.47 don't send the old version, so in that case
+  // apply every migration that matches the configuration
+  let old_version = old_version.and_then(parse_version);
+  for (version, migration) in migrations {
+    if let (Some(old_version), Some(version)) = (old_version, parse_version(version))
+      && old_version >= version
+    {
+      continue;
+    }
+    match migration {
+      ConfigMigration::Rename { from, to } => {
+        if let Some(value) = config.get(*from) {
+          // an explicitly set new key wins over the deprecated one
+          if !config.contains_key(*to) {
+            changes.push(config_change(to, ConfigChangeKind::Add(value.clone())));
+          }
+          changes.push(config_change(from, ConfigChangeKind::Remove));
+        }
+      }
+      ConfigMigration::Convert { key, convert } => {
+        if let Some(new_value) = config.get(*key).and_then(convert) {
+          changes.push(config_change(key, ConfigChangeKind::Set(new_value)));
+        }
+      }
+      ConfigMigration::Remove { key } => {
+        if config.contains_key(*key) {
+          changes.push(config_change(key, ConfigChangeKind::Remove));
+        }
+      }
+    }
+  }
+  changes
+}
+
+fn config_change(key: &str, kind: ConfigChangeKind) -> ConfigChange {
+  ConfigChange {
+    path: vec![key.to_string().into()],
+    kind,
+  }
+}
+
+/// Parses the `major.minor.patch` of a version, ignoring any pre-release or
+/// build suffix.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+  let core = version.split(['-', '+']).next()?;
+  let mut parts = core.split('.').map(|part| part.parse::<u32>().ok());
+  Some((parts.next()??, parts.next().unwrap_or(Some(0))?, parts.next().unwrap_or(Some(0))?))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn to_array(value: &ConfigKeyValue) -> Option<ConfigKeyValue> {
+    match value {
+      ConfigKeyValue::String(text) => Some(ConfigKeyValue::Array(text.split(',').map(|name| name.trim().into()).collect())),
+      _ => None,
+    }
+  }
+
+  const MIGRATIONS: &[(&str, ConfigMigration)] = &[
+    (
+      "0.6.0",
+      ConfigMigration::Rename {
+        from: "oldName",
+        to: "newName",
+      },
+    ),
+    (
+      "0.6.0",
+      ConfigMigration::Convert {
+        key: "fileNames",
+        convert: to_array,
+      },
+    ),
+    ("0.7.0", ConfigMigration::Remove { key: "obsolete" }),
+  ];
+
+  fn changes(old_version: Option<&str>, config: &[(&str, ConfigKeyValue)]) -> Vec<String> {
+    let config = config.iter().map(|(key, value)| (key.to_string(), value.clone())).collect::<ConfigKeyMap>();
+    get_config_changes(MIGRATIONS, old_version, &config)
+      .into_iter()
+      .map(|change| serde_json::to_string(&change).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn renames_deprecated_keys() {
+    assert_eq!(
+      changes(Some("0.5.0"), &[("oldName", true.into())]),
+      [r#"{"path":["newName"],"kind":"Add","value":true}"#, r#"{"path":["oldName"],"kind":"Remove"}"#]
+    );
+    // the new key is kept when both are set
+    assert_eq!(
+      changes(Some("0.5.0"), &[("oldName", true.into()), ("newName", false.into())]),
+      [r#"{"path":["oldName"],"kind":"Remove"}"#]
+    );
+  }
+
+  #[test]
+  fn converts_old_value_shapes() {
+    assert_eq!(
+      changes(Some("0.5.0"), &[("fileNames", "Dockerfile, Containerfile".into())]),
+      [r#"{"path":["fileNames"],"kind":"Set","value":["Dockerfile","Containerfile"]}"#]
+    );
+    assert!(changes(Some("0.5.0"), &[("fileNames", ConfigKeyValue::Array(vec!["Dockerfile".into()]))]).is_empty());
+  }
+
+  #[test]
+  fn removes_obsolete_keys() {
+    assert_eq!(changes(Some("0.6.2"), &[("obsolete", 1.into())]), [r#"{"path":["obsolete"],"kind":"Remove"}"#]);
+  }
+
+  #[test]
+  fn skips_migrations_the_old_version_already_has() {
+    let config = [("oldName", true.into()), ("obsolete", 1.into())];
+    assert_eq!(changes(Some("0.6.0"), &config), [r#"{"path":["obsolete"],"kind":"Remove"}"#]);
+    assert!(changes(Some("0.7.0"), &config).is_empty());
+    assert!(changes(Some("1.0.0-beta.1"), &config).is_empty());
+    // an unknown old version applies everything that matches
+    assert_eq!(changes(None, &config).len(), 3);
+  }
+
+  #[test]
+  fn parses_versions() {
+    assert_eq!(parse_version("0.5.0"), Some((0, 5, 0)));
+    assert_eq!(parse_version("1.2"), Some((1, 2, 0)));
+    assert_eq!(parse_version("1.2.3-alpha+build"), Some((1, 2, 3)));
+    assert_eq!(parse_version("latest"), None);
+  }
+}
@@ -1,4 +1,6 @@
 use dprint_core::configuration::NewLineKind;
+use dprint_core::configuration::ParseConfigurationError;
+use dprint_core::generate_str_to_from;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -10,4 +12,132 @@ pub struct Configuration {
   /// Whether to always break a `HEALTHCHECK` command onto its own continuation
   /// line when the instruction has options, even if it would fit on one line.
   pub healthcheck_cmd_new_line: bool,
+  /// Whether to always put the destination of a `COPY` or `ADD` instruction on
+  /// its own continuation line, even if the instruction would fit on one line.
+  pub copy_destination_new_line: bool,
+  /// Whether to put exactly one blank line before every `FROM` except the first,
+  /// keeping a comment block directly above the `FROM` with it.
+  pub blank_line_between_stages: bool,
+  /// The maximum number of consecutive blank lines to keep between
+  /// instructions. Longer runs are collapsed.
+  pub max_consecutive_blank_lines: u32,
+  /// The exact file names (ex. `Containerfile`) the plugin formats.
+  pub file_names: Vec<String>,
+  /// The `*.<extension>` patterns (ex. `*.dockerfile`) of files the plugin
+  /// formats.
+  pub file_patterns: Vec<String>,
+  /// The text of a comment that makes the instruction after it print exactly
+  /// as written.
+  pub ignore_node_comment_text: String,
+  /// The text of a comment at the top of a file that skips formatting the file.
+  pub ignore_file_comment_text: String,
+  /// Whether to re-parse the formatted text and fail with
+  /// [`crate::FormatError::SemanticChange`] if the meaning of any instruction
+  /// changed, instead of returning the output.
+  pub verify_semantics: bool,
+  /// Whether to parse shell-form `RUN` commands and print them with
+  /// consistent operator and redirection spacing and indented `if`, `for`,
+  /// `while` and `case` blocks. Commands that can't be parsed print as usual.
+  pub shell_formatting: bool,
+  /// The form to print `CMD` instructions in.
+  pub cmd_form: CommandForm,
+  /// The form to print `ENTRYPOINT` instructions in.
+  pub entrypoint_form: CommandForm,
+  /// How to quote the values of `ENV`, `LABEL` and `ARG` instructions.
+  pub quote_style: QuoteStyle,
+  /// How to lay out the pairs of `ENV` and `LABEL` instructions.
+  pub key_value_layout: KeyValueLayout,
+  /// Whether to put the flags of `FROM`, `COPY` and `HEALTHCHECK` instructions
+  /// in a canonical order. Instructions with a repeated flag keep their order.
+  pub sort_flags: bool,
+  /// Whether to write the durations and retries of `HEALTHCHECK` options in
+  /// their normal form (ex. `30s` for `0.5m`, or `1h30m` for `90m`).
+  pub normalize_healthcheck_options: bool,
+}
+
+/// The form of a `CMD` or `ENTRYPOINT` instruction's command.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandForm {
+  /// Keep the form the command was written in.
+  Maintain,
+  /// Rewrite shell-form commands as JSON arrays (ex. `CMD ["node", "app.js"]`)
+  /// when they're a plain list of words that doesn't need a shell, so signals
+  /// reach the process.
+  Exec,
+}
+
+generate_str_to_from![CommandForm, [Maintain, "maintain"], [Exec, "exec"]];
+
+/// How to quote the values of `ENV`, `LABEL` and `ARG` instructions.
+///
+/// Values are re-escaped for the quotes they end up in, so a `$` that single
+/// quotes kept literal is escaped as `\$` in double quotes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuoteStyle {
+  /// Keep values as written, except that `ENV` values with spaces are
+  /// double-quoted.
+  Maintain,
+  /// Use double quotes for values that are quoted or need quotes.
+  PreferDouble,
+  /// Double-quote every value.
+  AlwaysDouble,
+  /// Only quote values that need quotes, with single quotes when they avoid
+  /// escapes.
+  Minimal,
+}
+
+generate_str_to_from![
+  QuoteStyle,
+  [Maintain, "maintain"],
+  [PreferDouble, "preferDouble"],
+  [AlwaysDouble, "alwaysDouble"],
+  [Minimal, "minimal"]
+];
+
+/// How to lay out the pairs of `ENV` and `LABEL` instructions with more than
+/// one pair. Pairs with comments between them are always one per line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyValueLayout {
+  /// Keep the pairs on separate lines when the second pair was on its own line,
+  /// and otherwise put them on one line unless it exceeds the line width.
+  Maintain,
+  /// Put the pairs on one line when it fits within the line width, and
+  /// otherwise one per line.
+  FitOrBreakAll,
+  /// Always put each pair on its own line.
+  OnePerLine,
+}
+
+generate_str_to_from![
+  KeyValueLayout,
+  [Maintain, "maintain"],
+  [FitOrBreakAll, "fitOrBreakAll"],
+  [OnePerLine, "onePerLine"]
+];
+
+impl Configuration {
+  /// The file extensions matched by [`Configuration::file_patterns`], without
+  /// the leading `*.`.
+  pub fn file_extensions(&self) -> Vec<String> {
+    self
+      .file_patterns
+      .iter()
+      .filter_map(|pattern| pattern_extension(pattern))
+      .map(|ext| ext.to_string())
+      .collect()
+  }
+}
+
+/// Gets the extension of a `*.<extension>` file pattern, or `None` if the
+/// pattern has another shape.
+pub(super) fn pattern_extension(pattern: &str) -> Option<&str> {
+  let extension = pattern.strip_prefix("*.")?;
+  if extension.is_empty() || extension.contains(['*', '?', '/', '\\']) {
+    None
+  } else {
+    Some(extension)
+  }
 }
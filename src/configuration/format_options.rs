@@ -0,0 +1,157 @@
+use dprint_core::configuration::NewLineKind;
+use dprint_core::configuration::RECOMMENDED_GLOBAL_CONFIGURATION;
+use std::path::Path;
+
+use super::CommandForm;
+use super::Configuration;
+use super::KeyValueLayout;
+use super::QuoteStyle;
+use super::configuration::pattern_extension;
+
+/// The file names the plugin formats when `fileNames` isn't set.
+pub(super) const DEFAULT_FILE_NAMES: &[&str] = &["Dockerfile", "Containerfile"];
+/// The file patterns the plugin formats when `filePatterns` isn't set.
+pub(super) const DEFAULT_FILE_PATTERNS: &[&str] = &["*.dockerfile", "*.containerfile"];
+
+/// Whether the name at the end of `path` is one of the default file names, a
+/// variant of one such as `Dockerfile.dev`, or matches one of the default file
+/// patterns. dprint only matches the variants through an `associations` entry,
+/// since they can't be written as `fileNames` or `filePatterns`.
+///
+/// Extensions match in any case (ex. `web.Dockerfile`), while the names are
+/// case-sensitive like Docker's default of `Dockerfile`.
+pub fn is_dockerfile_name(path: &Path) -> bool {
+  let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+    return false;
+  };
+  let is_named = |base: &str| name.strip_prefix(base).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+  let has_extension = |extension: &str| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension));
+  DEFAULT_FILE_NAMES.iter().any(|base| is_named(base)) || DEFAULT_FILE_PATTERNS.iter().filter_map(|pattern| pattern_extension(pattern)).any(has_extension)
+}
+
+/// The options that change how a Dockerfile is formatted, for using the
+/// formatter as a library with [`crate::format_str`]. See [`Configuration`]
+/// for what each option does; dprint's configuration is resolved into one of
+/// these by [`super::resolve_config`].
+///
+/// Start from [`FormatOptions::default`] and set the options to change, since
+/// more options can be added without a breaking change.
+///
+/// # Example
+///
+/// ```
+/// use dprint_plugin_dockerfile::configuration::*;
+///
+/// let mut options = FormatOptions::default();
+/// options.line_width = 80;
+/// options.quote_style = QuoteStyle::PreferDouble;
+/// let formatted = dprint_plugin_dockerfile::format_str("from   alpine", &options).unwrap();
+/// assert_eq!(formatted.as_deref(), Some("FROM alpine\n"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+  pub line_width: u32,
+  pub new_line_kind: NewLineKind,
+  pub healthcheck_cmd_new_line: bool,
+  pub copy_destination_new_line: bool,
+  pub blank_line_between_stages: bool,
+  pub max_consecutive_blank_lines: u32,
+  pub ignore_node_comment_text: String,
+  pub ignore_file_comment_text: String,
+  pub verify_semantics: bool,
+  pub shell_formatting: bool,
+  pub cmd_form: CommandForm,
+  pub entrypoint_form: CommandForm,
+  pub quote_style: QuoteStyle,
+  pub key_value_layout: KeyValueLayout,
+  pub sort_flags: bool,
+  pub normalize_healthcheck_options: bool,
+}
+
+impl Default for FormatOptions {
+  fn default() -> Self {
+    FormatOptions {
+      line_width: RECOMMENDED_GLOBAL_CONFIGURATION.line_width,
+      new_line_kind: RECOMMENDED_GLOBAL_CONFIGURATION.new_line_kind,
+      healthcheck_cmd_new_line: false,
+      copy_destination_new_line: false,
+      blank_line_between_stages: false,
+      max_consecutive_blank_lines: 1,
+      ignore_node_comment_text: "dprint-ignore".to_string(),
+      ignore_file_comment_text: "dprint-ignore-file".to_string(),
+      verify_semantics: false,
+      shell_formatting: false,
+      cmd_form: CommandForm::Maintain,
+      entrypoint_form: CommandForm::Maintain,
+      quote_style: QuoteStyle::Maintain,
+      key_value_layout: KeyValueLayout::Maintain,
+      sort_flags: false,
+      normalize_healthcheck_options: false,
+    }
+  }
+}
+
+impl From<FormatOptions> for Configuration {
+  /// Makes a configuration that formats with `options` and matches the
+  /// default file names and patterns.
+  fn from(options: FormatOptions) -> Self {
+    Configuration {
+      line_width: options.line_width,
+      new_line_kind: options.new_line_kind,
+      healthcheck_cmd_new_line: options.healthcheck_cmd_new_line,
+      copy_destination_new_line: options.copy_destination_new_line,
+      blank_line_between_stages: options.blank_line_between_stages,
+      max_consecutive_blank_lines: options.max_consecutive_blank_lines,
+      file_names: DEFAULT_FILE_NAMES.iter().map(|name| name.to_string()).collect(),
+      file_patterns: DEFAULT_FILE_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+      ignore_node_comment_text: options.ignore_node_comment_text,
+      ignore_file_comment_text: options.ignore_file_comment_text,
+      verify_semantics: options.verify_semantics,
+      shell_formatting: options.shell_formatting,
+      cmd_form: options.cmd_form,
+      entrypoint_form: options.entrypoint_form,
+      quote_style: options.quote_style,
+      key_value_layout: options.key_value_layout,
+      sort_flags: options.sort_flags,
+      normalize_healthcheck_options: options.normalize_healthcheck_options,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use dprint_core::configuration::ConfigKeyMap;
+  use dprint_core::configuration::GlobalConfiguration;
+
+  use super::*;
+  use crate::configuration::resolve_config;
+
+  #[test]
+  fn defaults_match_resolved_config() {
+    let resolved = resolve_config(ConfigKeyMap::new(), &GlobalConfiguration::default()).config;
+    let from_options = Configuration::from(FormatOptions::default());
+    // Configuration has no PartialEq, and its serialized form covers every field
+    assert_eq!(serde_json::to_value(&from_options).unwrap(), serde_json::to_value(&resolved).unwrap());
+  }
+
+  #[test]
+  fn recognizes_dockerfile_names() {
+    for (path, expected) in [
+      ("Dockerfile", true),
+      ("app/web.dockerfile", true),
+      ("web.Dockerfile", true),
+      ("Dockerfile.dev", true),
+      ("docker/Dockerfile.prod", true),
+      ("Containerfile", true),
+      ("Containerfile.arm64", true),
+      ("/src/base.containerfile", true),
+      ("dockerfile", false),
+      ("Dockerfiles", false),
+      ("docker-compose.yml", false),
+      ("", false),
+    ] {
+      assert_eq!(is_dockerfile_name(Path::new(path)), expected, "{}", path);
+    }
+  }
+}
@@ -1,8 +1,17 @@
 mod builder;
+#[cfg(feature = "wasm")]
+mod config_updates;
 #[allow(clippy::module_inception)]
 mod configuration;
+mod format_options;
 mod resolve_config;
 
 pub use builder::*;
+#[cfg(feature = "wasm")]
+pub use config_updates::*;
 pub use configuration::*;
+pub use format_options::*;
 pub use resolve_config::*;
+
+/// Re-exported so [`FormatOptions::new_line_kind`] can be set without depending on dprint-core.
+pub use dprint_core::configuration::NewLineKind;
@@ -1,4 +1,8 @@
 use super::Configuration;
+use super::FormatOptions;
+use super::configuration::pattern_extension;
+use super::format_options::DEFAULT_FILE_NAMES;
+use super::format_options::DEFAULT_FILE_PATTERNS;
 use dprint_core::configuration::*;
 
 /// Resolves configuration from a collection of key value strings.
@@ -26,23 +30,60 @@ use dprint_core::configuration::*;
 pub fn resolve_config(config: ConfigKeyMap, global_config: &GlobalConfiguration) -> ResolveConfigurationResult<Configuration> {
   let mut diagnostics = Vec::new();
   let mut config = config;
+  let defaults = FormatOptions::default();
 
   let resolved_config = Configuration {
     line_width: get_value(
       &mut config,
       "lineWidth",
-      global_config.line_width.unwrap_or(RECOMMENDED_GLOBAL_CONFIGURATION.line_width),
+      global_config.line_width.unwrap_or(defaults.line_width),
       &mut diagnostics,
     ),
     new_line_kind: get_value(
       &mut config,
       "newLineKind",
-      global_config.new_line_kind.unwrap_or(RECOMMENDED_GLOBAL_CONFIGURATION.new_line_kind),
+      global_config.new_line_kind.unwrap_or(defaults.new_line_kind),
+      &mut diagnostics,
+    ),
+    healthcheck_cmd_new_line: get_value(&mut config, "healthcheckCmdNewLine", defaults.healthcheck_cmd_new_line, &mut diagnostics),
+    copy_destination_new_line: get_value(&mut config, "copyDestinationNewLine", defaults.copy_destination_new_line, &mut diagnostics),
+    blank_line_between_stages: get_value(&mut config, "blankLineBetweenStages", defaults.blank_line_between_stages, &mut diagnostics),
+    max_consecutive_blank_lines: get_value(&mut config, "maxConsecutiveBlankLines", defaults.max_consecutive_blank_lines, &mut diagnostics),
+    file_names: get_string_vec(&mut config, "fileNames", &mut diagnostics).unwrap_or_else(|| DEFAULT_FILE_NAMES.iter().map(|name| name.to_string()).collect()),
+    file_patterns: get_string_vec(&mut config, "filePatterns", &mut diagnostics)
+      .unwrap_or_else(|| DEFAULT_FILE_PATTERNS.iter().map(|pattern| pattern.to_string()).collect()),
+    ignore_node_comment_text: get_value(&mut config, "ignoreNodeCommentText", defaults.ignore_node_comment_text, &mut diagnostics),
+    ignore_file_comment_text: get_value(&mut config, "ignoreFileCommentText", defaults.ignore_file_comment_text, &mut diagnostics),
+    verify_semantics: get_value(&mut config, "verifySemantics", defaults.verify_semantics, &mut diagnostics),
+    shell_formatting: get_value(&mut config, "shellFormatting", defaults.shell_formatting, &mut diagnostics),
+    cmd_form: get_value(&mut config, "cmdForm", defaults.cmd_form, &mut diagnostics),
+    entrypoint_form: get_value(&mut config, "entrypointForm", defaults.entrypoint_form, &mut diagnostics),
+    quote_style: get_value(&mut config, "quoteStyle", defaults.quote_style, &mut diagnostics),
+    key_value_layout: get_value(&mut config, "keyValueLayout", defaults.key_value_layout, &mut diagnostics),
+    sort_flags: get_value(&mut config, "sortFlags", defaults.sort_flags, &mut diagnostics),
+    normalize_healthcheck_options: get_value(
+      &mut config,
+      "normalizeHealthcheckOptions",
+      defaults.normalize_healthcheck_options,
       &mut diagnostics,
     ),
-    healthcheck_cmd_new_line: get_value(&mut config, "healthcheckCmdNewLine", false, &mut diagnostics),
   };
 
+  // dprint matches files by extension or exact name only, so other patterns
+  // could never take effect. Associations replace the plugin's own matching,
+  // so the suggested entry has to list the default files as well
+  for pattern in &resolved_config.file_patterns {
+    if pattern_extension(pattern).is_none() {
+      diagnostics.push(ConfigurationDiagnostic {
+        property_name: "filePatterns".to_string(),
+        message: format!(
+          "Unsupported pattern '{}'. Only patterns of the form '*.<extension>' are supported; list other files in 'fileNames', or match them with a '\"**/{}\"' entry in dprint's 'associations' for the plugin alongside the default files.",
+          pattern, pattern
+        ),
+      });
+    }
+  }
+
   diagnostics.extend(get_unknown_property_diagnostics(config));
 
   ResolveConfigurationResult {
@@ -50,3 +91,21 @@ pub fn resolve_config(config: ConfigKeyMap, global_config: &GlobalConfiguration)
     diagnostics,
   }
 }
+
+fn get_string_vec(config: &mut ConfigKeyMap, key: &str, diagnostics: &mut Vec<ConfigurationDiagnostic>) -> Option<Vec<String>> {
+  get_nullable_vec(
+    config,
+    key,
+    |value, i, diagnostics| match value {
+      ConfigKeyValue::String(value) => Some(value),
+      _ => {
+        diagnostics.push(ConfigurationDiagnostic {
+          property_name: format!("{}[{}]", key, i),
+          message: "Expected a string.".to_string(),
+        });
+        None
+      }
+    },
+    diagnostics,
+  )
+}
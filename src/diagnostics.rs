@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::ast::Dockerfile;
+use crate::ast::Instruction;
+use crate::ast::ShellOrExecExpr;
+use crate::ast::Span;
+use crate::configuration::CommandForm;
+use crate::configuration::Configuration;
+use crate::exec_form::exec_form_arguments;
+use crate::flag_order::sort_flags;
+use crate::healthcheck::check_option;
+
+/// A problem in a Dockerfile that formatting leaves in place, such as an
+/// invalid `HEALTHCHECK` option or a command that `cmdForm` had to keep in
+/// shell form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+  /// The span of the text the diagnostic is about.
+  pub span: Span,
+  pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+/// Gets the diagnostics for a Dockerfile formatted with `config`, in the order
+/// of their spans.
+pub fn diagnostics(file: &Dockerfile, config: &Configuration) -> Vec<Diagnostic> {
+  let mut diagnostics = Vec::new();
+  for instruction in &file.instructions {
+    healthcheck_diagnostics(instruction, &mut diagnostics);
+    if let Instruction::Onbuild(node) = instruction
+      && let Some(trigger) = forbidden_trigger(&node.instruction)
+    {
+      diagnostics.push(Diagnostic {
+        span: instruction.span(),
+        message: format!("{} isn't allowed as an ONBUILD trigger", trigger),
+      });
+    }
+    if config.sort_flags {
+      repeated_flag_diagnostics(instruction, &mut diagnostics);
+    }
+    let (keyword, expr, form) = match instruction {
+      Instruction::Cmd(node) => ("CMD", &node.expr, config.cmd_form),
+      Instruction::Entrypoint(node) => ("ENTRYPOINT", &node.expr, config.entrypoint_form),
+      _ => continue,
+    };
+    if let (ShellOrExecExpr::Shell(value), CommandForm::Exec) = (expr, form)
+      && let Err(reason) = exec_form_arguments(file, value, keyword == "CMD")
+    {
+      diagnostics.push(Diagnostic {
+        span: instruction.span(),
+        message: format!("{} was left in shell form because {}", keyword, reason),
+      });
+    }
+  }
+  diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+  diagnostics
+}
+
+/// The keyword of an `ONBUILD` trigger that Docker rejects.
+fn forbidden_trigger(trigger: &Instruction) -> Option<&'static str> {
+  match trigger {
+    Instruction::Onbuild(_) => Some("ONBUILD"),
+    Instruction::From(_) => Some("FROM"),
+    // including an `ONBUILD` or `FROM` trigger that didn't parse
+    Instruction::Misc(node) => ["ONBUILD", "FROM", "MAINTAINER"]
+      .into_iter()
+      .find(|keyword| node.instruction.content.eq_ignore_ascii_case(keyword)),
+    _ => None,
+  }
+}
+
+/// Reports the `HEALTHCHECK` options that Docker would reject when building.
+fn healthcheck_diagnostics(instruction: &Instruction, diagnostics: &mut Vec<Diagnostic>) {
+  let flags = match instruction {
+    Instruction::Healthcheck(node) => &node.flags,
+    Instruction::Onbuild(node) => return healthcheck_diagnostics(&node.instruction, diagnostics),
+    _ => return,
+  };
+  for flag in flags {
+    if let Err(message) = check_option(&flag.name.content, &flag.value.content) {
+      diagnostics.push(Diagnostic { span: flag.span, message });
+    }
+  }
+}
+
+fn repeated_flag_diagnostics(instruction: &Instruction, diagnostics: &mut Vec<Diagnostic>) {
+  let (keyword, flags) = match instruction {
+    Instruction::From(node) => ("FROM", node.flags.iter().map(|flag| (&flag.name, flag.span)).collect::<Vec<_>>()),
+    Instruction::Copy(node) => ("COPY", node.flags.iter().map(|flag| (&flag.name, flag.span)).collect()),
+    Instruction::Healthcheck(node) => ("HEALTHCHECK", node.flags.iter().map(|flag| (&flag.name, flag.span)).collect()),
+    Instruction::Onbuild(node) => return repeated_flag_diagnostics(&node.instruction, diagnostics),
+    Instruction::Heredoc(node) => return repeated_flag_diagnostics(&node.instruction, diagnostics),
+    _ => return,
+  };
+  if let Err(index) = sort_flags(keyword, &flags, |(name, _)| name) {
+    let (name, span) = flags[index];
+    diagnostics.push(Diagnostic {
+      span,
+      message: format!("the flags of {} were left in their order because --{} is repeated", keyword, name.content),
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::configuration::ConfigurationBuilder;
+
+  #[test]
+  fn reports_commands_left_in_shell_form() {
+    let file = Dockerfile::parse("FROM a\nENTRYPOINT node $APP\nCMD --port=80\n").unwrap();
+    let config = ConfigurationBuilder::new().entrypoint_form(CommandForm::Exec).build();
+    assert_eq!(
+      diagnostics(&file, &config),
+      [Diagnostic {
+        span: Span::new(7, 27),
+        message: "ENTRYPOINT was left in shell form because it uses shell features".to_string(),
+      }]
+    );
+    let config = ConfigurationBuilder::new().cmd_form(CommandForm::Exec).build();
+    assert_eq!(
+      diagnostics(&file, &config)[0].to_string(),
+      "CMD was left in shell form because the stage's ENTRYPOINT receives it as arguments"
+    );
+    assert_eq!(diagnostics(&file, &ConfigurationBuilder::new().build()), []);
+    let file = Dockerfile::parse("FROM a\nENTRYPOINT server --verbose\nCMD [\"--port\", \"80\"]\n").unwrap();
+    let config = ConfigurationBuilder::new().entrypoint_form(CommandForm::Exec).build();
+    assert_eq!(
+      diagnostics(&file, &config)[0].to_string(),
+      "ENTRYPOINT was left in shell form because the stage's CMD would be passed to it as arguments"
+    );
+  }
+
+  #[test]
+  fn reports_forbidden_onbuild_triggers() {
+    let file = Dockerfile::parse("ONBUILD ONBUILD RUN x\nONBUILD from alpine\nONBUILD MAINTAINER me\nONBUILD RUN x\n").unwrap();
+    let diagnostics = diagnostics(&file, &ConfigurationBuilder::new().build());
+    assert_eq!(
+      diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(),
+      [
+        "ONBUILD isn't allowed as an ONBUILD trigger",
+        "FROM isn't allowed as an ONBUILD trigger",
+        "MAINTAINER isn't allowed as an ONBUILD trigger",
+      ]
+    );
+    assert_eq!(diagnostics[1].span, Span::new(22, 41));
+    let file = Dockerfile::parse("ONBUILD FROM\nONBUILD FROM --platform=x\nONBUILD COPY onlyone\n").unwrap();
+    assert_eq!(
+      super::diagnostics(&file, &ConfigurationBuilder::new().build())
+        .iter()
+        .map(|diagnostic| (diagnostic.span, diagnostic.to_string()))
+        .collect::<Vec<_>>(),
+      [
+        (Span::new(0, 12), "FROM isn't allowed as an ONBUILD trigger".to_string()),
+        (Span::new(13, 38), "FROM isn't allowed as an ONBUILD trigger".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn reports_invalid_healthcheck_options() {
+    let file = Dockerfile::parse("HEALTHCHECK --interval=30 --retries=3 --start-perod=5s CMD x\nONBUILD HEALTHCHECK --timeout=0.5ms CMD x\n").unwrap();
+    let messages = diagnostics(&file, &ConfigurationBuilder::new().build())
+      .into_iter()
+      .map(|diagnostic| diagnostic.to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      messages,
+      [
+        "--interval=30 is not a valid duration (ex. 30s or 1m30s)",
+        "--start-perod is not a HEALTHCHECK option",
+        "--timeout has to be 0 or at least 1ms",
+      ]
+    );
+    assert_eq!(diagnostics(&file, &ConfigurationBuilder::new().build())[0].span, Span::new(12, 25));
+  }
+
+  #[test]
+  fn reports_repeated_flags() {
+    let file = Dockerfile::parse(
+      "FROM a
+COPY --chown=a --exclude=x --exclude=y --chown=b x /y
+ONBUILD HEALTHCHECK --retries=1 --retries=2 CMD x
+",
+    )
+    .unwrap();
+    let config = ConfigurationBuilder::new().sort_flags(true).build();
+    assert_eq!(
+      diagnostics(&file, &config),
+      [
+        Diagnostic {
+          span: Span::new(46, 55),
+          message: "the flags of COPY were left in their order because --chown is repeated".to_string(),
+        },
+        Diagnostic {
+          span: Span::new(93, 104),
+          message: "the flags of HEALTHCHECK were left in their order because --retries is repeated".to_string(),
+        },
+      ]
+    );
+    assert_eq!(diagnostics(&file, &ConfigurationBuilder::new().build()), []);
+  }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 pub use monch::ParseErrorFailureError as ParseError;
 
 /// An error that can occur while formatting a Dockerfile.
@@ -6,4 +8,26 @@ pub enum FormatError {
   /// The input could not be parsed as a Dockerfile.
   #[error(transparent)]
   Parse(#[from] ParseError),
+  /// Formatting changed the meaning of an instruction (found when
+  /// `verifySemantics` is enabled). This is a bug in the formatter.
+  #[error(
+    "formatting changed the meaning of the instruction on line {line}, so the file was left unformatted (please report this as a bug)\n  before: {before}\n  after:  {after}"
+  )]
+  SemanticChange {
+    /// The 1-indexed line of the instruction in the original text.
+    line: usize,
+    /// The semantic form of the original instruction.
+    before: String,
+    /// The semantic form of the formatted instruction.
+    after: String,
+  },
+  /// The file given to [`crate::format_file`] couldn't be read.
+  #[error("{}: {source}", path.display())]
+  Io { path: PathBuf, source: std::io::Error },
+  /// The file given to [`crate::format_file`] isn't named like a Dockerfile.
+  #[error(
+    "{} isn't named like a Dockerfile (Dockerfile, Containerfile, Dockerfile.*, Containerfile.*, *.dockerfile or *.containerfile)",
+    .0.display()
+  )]
+  UnrecognizedFileName(PathBuf),
 }
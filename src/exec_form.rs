@@ -0,0 +1,252 @@
+use crate::ast::BreakableString;
+use crate::ast::BreakableStringComponent;
+use crate::ast::Dockerfile;
+use crate::ast::Instruction;
+use crate::shell::ShellTokenKind;
+use crate::shell::tokenize;
+
+/// Words that can't start a command in exec form: shell keywords, and builtins
+/// that have no executable of their own or only affect the shell.
+pub(crate) const SHELL_ONLY_COMMANDS: &[&str] = &[
+  "!", "{", "}", "[[", "]]", "case", "do", "done", "elif", "else", "esac", "fi", "for", "function", "if", "in", "select", "then", "time", "until", "while",
+  ".", ":", "alias", "break", "cd", "continue", "declare", "eval", "exec", "exit", "export", "local", "read", "readonly", "return", "set", "shift", "source",
+  "times", "trap", "typeset", "ulimit", "umask", "unalias", "unset", "wait",
+];
+
+/// Gets the arguments of the exec form of a shell-form `CMD` or `ENTRYPOINT`
+/// command in `file`, or the reason it has to stay in shell form.
+///
+/// The command has to be a plain list of words with no expansions, globs,
+/// operators or redirections, which runs the same without `/bin/sh -c`.
+pub(crate) fn exec_form_arguments(file: &Dockerfile, value: &BreakableString, is_cmd: bool) -> Result<Vec<String>, &'static str> {
+  if file.instructions.iter().any(|instruction| matches!(instruction, Instruction::Shell(_))) {
+    return Err("a SHELL instruction changes the shell that runs it");
+  }
+  let stages = stage_chain(file, value.span.start);
+  if is_cmd
+    && stages
+      .iter()
+      .any(|stage| stage.iter().any(|instruction| matches!(instruction, Instruction::Entrypoint(_))))
+  {
+    // the ENTRYPOINT gets `/bin/sh -c <command>` as its arguments
+    return Err("the stage's ENTRYPOINT receives it as arguments");
+  }
+  if value
+    .components
+    .iter()
+    .any(|component| matches!(component, BreakableStringComponent::Comment(_)))
+  {
+    return Err("it has comments between its lines");
+  }
+  let arguments = shell_words(value).ok_or("it uses shell features")?;
+  match arguments.first() {
+    None => Err("it's empty"),
+    Some(command) if SHELL_ONLY_COMMANDS.contains(&command.as_str()) => Err("it runs a shell keyword or builtin"),
+    // a shell-form ENTRYPOINT ignores CMD, but an exec-form one appends it.
+    // An ENTRYPOINT resets the CMD it inherits, so only the stage's own counts
+    Some(_) if !is_cmd && stages[0].iter().any(|instruction| matches!(instruction, Instruction::Cmd(_))) => {
+      Err("the stage's CMD would be passed to it as arguments")
+    }
+    Some(_) => Ok(arguments),
+  }
+}
+
+/// The instructions of the stage that `offset` is in, followed by those of the
+/// earlier stages it's built on through `FROM <stage>`, nearest first.
+fn stage_chain(file: &Dockerfile, offset: usize) -> Vec<&[Instruction]> {
+  let instructions = &file.instructions;
+  let froms = instructions
+    .iter()
+    .enumerate()
+    .filter_map(|(index, instruction)| match instruction {
+      Instruction::From(node) => Some((index, node)),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  let stage_instructions = |stage: usize| {
+    let end = froms.get(stage + 1).map(|(index, _)| *index).unwrap_or(instructions.len());
+    &instructions[froms[stage].0 + 1..end]
+  };
+  let Some(mut stage) = froms.iter().rposition(|(index, _)| instructions[*index].span().start < offset) else {
+    // before the first FROM, where there's no stage to inherit from
+    return vec![&instructions[..froms.first().map(|(index, _)| *index).unwrap_or(instructions.len())]];
+  };
+  let mut chain = vec![stage_instructions(stage)];
+  // a stage can only be built on an earlier one, so the chain ends
+  while let Some(base) = froms[..stage].iter().rposition(|(_, node)| {
+    node
+      .alias
+      .as_ref()
+      .is_some_and(|alias| alias.content.eq_ignore_ascii_case(&froms[stage].1.image.content))
+  }) {
+    stage = base;
+    chain.push(stage_instructions(stage));
+  }
+  chain
+}
+
+/// Splits a command into the words the shell would pass to it, or `None` if
+/// it uses any shell feature beyond quoting and escaping.
+fn shell_words(value: &BreakableString) -> Option<Vec<String>> {
+  let mut words = Vec::new();
+  let mut word: Option<String> = None;
+  for token in tokenize(value) {
+    let text = token.text.as_str();
+    let part = match token.kind {
+      ShellTokenKind::Whitespace => {
+        words.extend(word.take());
+        continue;
+      }
+      ShellTokenKind::Word => {
+        // globs, tilde and brace expansion, and `NAME=value` assignments
+        let is_assignment = words.is_empty() && word.is_none() && text.contains('=');
+        if is_assignment || text.contains(['*', '?', '[', '~', '{', '}']) {
+          return None;
+        }
+        unescape(text, |_| true)
+      }
+      ShellTokenKind::SingleQuoted if token.terminated => text[1..text.len() - 1].to_string(),
+      ShellTokenKind::DoubleQuoted if token.terminated && !text.contains(['$', '`']) => {
+        unescape(&text[1..text.len() - 1], |c| matches!(c, '"' | '\\' | '$' | '`'))
+      }
+      _ => return None,
+    };
+    word.get_or_insert_with(String::new).push_str(&part);
+  }
+  words.extend(word);
+  Some(words)
+}
+
+/// Removes the backslashes that escape the characters matching `is_escaped`.
+fn unescape(text: &str, is_escaped: impl Fn(char) -> bool) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '\\'
+      && let Some(next) = chars.next_if(|next| is_escaped(*next))
+    {
+      result.push(next);
+      continue;
+    }
+    result.push(c);
+  }
+  result
+}
+
+/// Quotes a string as a JSON string, the reverse of how exec-form elements are
+/// unquoted.
+pub(crate) fn json_string(value: &str) -> String {
+  let mut result = String::with_capacity(value.len() + 2);
+  result.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => result.push_str("\\\""),
+      '\\' => result.push_str("\\\\"),
+      '\n' => result.push_str("\\n"),
+      '\t' => result.push_str("\\t"),
+      '\r' => result.push_str("\\r"),
+      '\u{0008}' => result.push_str("\\b"),
+      '\u{000C}' => result.push_str("\\f"),
+      c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+      c => result.push(c),
+    }
+  }
+  result.push('"');
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::ast::CmdInstruction;
+  use crate::ast::EntrypointInstruction;
+  use crate::ast::ShellOrExecExpr;
+
+  /// Gets the exec-form arguments of the last shell-form command in `text`.
+  fn arguments(text: &str) -> Result<Vec<String>, &'static str> {
+    let file = Dockerfile::parse(text).unwrap();
+    let (value, is_cmd) = file
+      .instructions
+      .iter()
+      .rev()
+      .find_map(|instruction| match instruction {
+        Instruction::Cmd(CmdInstruction {
+          expr: ShellOrExecExpr::Shell(value),
+          ..
+        }) => Some((value, true)),
+        Instruction::Entrypoint(EntrypointInstruction {
+          expr: ShellOrExecExpr::Shell(value),
+          ..
+        }) => Some((value, false)),
+        _ => None,
+      })
+      .unwrap();
+    exec_form_arguments(&file, value, is_cmd)
+  }
+
+  #[test]
+  fn gets_the_words_of_plain_commands() {
+    assert_eq!(arguments("CMD node   app.js --port=80").unwrap(), ["node", "app.js", "--port=80"]);
+    assert_eq!(
+      arguments(r#"CMD echo 'a  $b' "c \"d\" \q" e\ f"g""#).unwrap(),
+      ["echo", "a  $b", r#"c "d" \q"#, "e fg"]
+    );
+    assert_eq!(arguments("CMD nginx \\\n  -g daemon").unwrap(), ["nginx", "-g", "daemon"]);
+  }
+
+  #[test]
+  fn keeps_commands_that_need_a_shell() {
+    for text in [
+      "CMD node $APP",
+      "CMD echo \"$HOME\"",
+      "CMD a && b",
+      "CMD a > log",
+      "CMD rm *.tmp",
+      "CMD ls ~",
+      "CMD PORT=80 node app.js",
+      "CMD echo `date`",
+      "CMD echo 'a",
+      "CMD a # comment",
+    ] {
+      assert!(arguments(text).is_err(), "{}", text);
+    }
+    assert_eq!(arguments("CMD cd /app"), Err("it runs a shell keyword or builtin"));
+    assert_eq!(arguments("CMD node \\\n  # comment\n  app.js"), Err("it has comments between its lines"));
+    assert_eq!(
+      arguments("SHELL [\"pwsh\"]\nCMD node app.js"),
+      Err("a SHELL instruction changes the shell that runs it")
+    );
+  }
+
+  #[test]
+  fn keeps_cmd_after_an_entrypoint_in_its_stage() {
+    assert!(arguments("FROM a\nENTRYPOINT [\"x\"]\nCMD node app.js").is_err());
+    assert!(arguments("FROM a\nENTRYPOINT [\"x\"]\nFROM b\nCMD node app.js").is_ok());
+    assert!(arguments("FROM a\nENTRYPOINT [\"x\"]\nFROM b\nENTRYPOINT node app.js").is_ok());
+  }
+
+  #[test]
+  fn keeps_cmd_after_an_inherited_entrypoint() {
+    let text = "FROM alpine AS base\nENTRYPOINT [\"/x\"]\nFROM base AS middle\nRUN x\nFROM other\nFROM MIDDLE\nCMD node app.js";
+    assert_eq!(arguments(text), Err("the stage's ENTRYPOINT receives it as arguments"));
+    assert!(arguments("FROM alpine AS base\nENTRYPOINT [\"/x\"]\nFROM base2\nCMD node app.js").is_ok());
+    // only earlier stages can be built on
+    assert!(arguments("FROM base\nCMD node app.js\nFROM alpine AS base\nENTRYPOINT [\"/x\"]").is_ok());
+  }
+
+  #[test]
+  fn keeps_entrypoint_with_a_cmd_in_its_stage() {
+    assert_eq!(
+      arguments("FROM a\nCMD [\"--port\", \"80\"]\nENTRYPOINT server --verbose"),
+      Err("the stage's CMD would be passed to it as arguments")
+    );
+    assert!(arguments("FROM a\nCMD [\"--port\", \"80\"]\nFROM a\nENTRYPOINT server --verbose").is_ok());
+    // the ENTRYPOINT resets an inherited CMD
+    assert!(arguments("FROM a AS base\nCMD [\"--port\", \"80\"]\nFROM base\nENTRYPOINT server --verbose").is_ok());
+  }
+
+  #[test]
+  fn quotes_json_strings() {
+    assert_eq!(json_string("a\"b\\c\n\u{1}é"), r#""a\"b\\c\n\u0001é""#);
+  }
+}
@@ -0,0 +1,76 @@
+//! The canonical order `sortFlags` puts the flags of `FROM`, `COPY` and
+//! `HEALTHCHECK` instructions in.
+
+use crate::ast::SpannedString;
+
+const FROM_FLAGS: &[&str] = &["platform"];
+const COPY_FLAGS: &[&str] = &["from", "chown", "chmod", "link", "parents", "exclude"];
+const HEALTHCHECK_FLAGS: &[&str] = &["interval", "timeout", "start-period", "start-interval", "retries"];
+
+/// Flags that can be given more than once, which keep their relative order.
+const REPEATABLE_FLAGS: &[&str] = &["exclude"];
+
+/// Sorts the flags of a `keyword` instruction into the canonical order, with
+/// unknown flags after the known ones in the order they were written. Returns
+/// the index of the first repeated flag instead when a flag that can only be
+/// given once is repeated, since which one takes effect depends on the order.
+pub(crate) fn sort_flags<'a, T>(keyword: &str, flags: &'a [T], name: impl Fn(&T) -> &SpannedString) -> Result<Vec<&'a T>, usize> {
+  if let Some(index) = repeated_flag(flags, &name) {
+    return Err(index);
+  }
+  let order = match keyword {
+    "FROM" => FROM_FLAGS,
+    "COPY" => COPY_FLAGS,
+    "HEALTHCHECK" => HEALTHCHECK_FLAGS,
+    _ => &[],
+  };
+  let mut sorted = flags.iter().collect::<Vec<_>>();
+  sorted.sort_by_key(|flag| {
+    let name = name(flag).content.as_str();
+    order.iter().position(|known| *known == name).unwrap_or(order.len())
+  });
+  Ok(sorted)
+}
+
+fn repeated_flag<T>(flags: &[T], name: impl Fn(&T) -> &SpannedString) -> Option<usize> {
+  (1..flags.len()).find(|i| {
+    let flag_name = &name(&flags[*i]).content;
+    !REPEATABLE_FLAGS.contains(&flag_name.as_str()) && flags[..*i].iter().any(|flag| name(flag).content == *flag_name)
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::ast::Span;
+
+  fn sort(keyword: &str, names: &[&str]) -> Result<Vec<String>, usize> {
+    let names = names
+      .iter()
+      .map(|name| SpannedString {
+        span: Span::new(0, 0),
+        content: name.to_string(),
+      })
+      .collect::<Vec<_>>();
+    sort_flags(keyword, &names, |name| name).map(|sorted| sorted.into_iter().map(|name| name.content.clone()).collect())
+  }
+
+  #[test]
+  fn sorts_flags_into_the_canonical_order() {
+    assert_eq!(sort("COPY", &["link", "chown", "from"]).unwrap(), ["from", "chown", "link"]);
+    assert_eq!(
+      sort("COPY", &["exclude", "keep", "from", "exclude"]).unwrap(),
+      ["from", "exclude", "exclude", "keep"]
+    );
+    assert_eq!(
+      sort("HEALTHCHECK", &["retries", "start-period", "interval"]).unwrap(),
+      ["interval", "start-period", "retries"]
+    );
+  }
+
+  #[test]
+  fn keeps_repeated_flags_in_their_order() {
+    assert_eq!(sort("COPY", &["chown", "from", "chown"]), Err(2));
+    assert_eq!(sort("FROM", &["platform", "platform"]), Err(1));
+  }
+}
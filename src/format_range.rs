@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+use crate::ast::Dockerfile;
+use crate::ast::Span;
+use crate::configuration::Configuration;
+use crate::error::FormatError;
+use crate::format_text::config_to_print_options;
+use crate::format_text::parse_node;
+use crate::format_text::strip_bom;
+use crate::generation::generate_range;
+use crate::generation::is_file_ignored;
+use crate::generation::parse_comments;
+
+/// A replacement of a byte range of the original text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+  /// The byte range of the original text to replace.
+  pub range: Range<usize>,
+  /// The text to insert in place of the range.
+  pub new_text: String,
+}
+
+impl TextEdit {
+  /// Applies the edit to the text it was computed from.
+  pub fn apply(&self, text: &str) -> String {
+    let mut result = String::with_capacity(text.len() - self.range.len() + self.new_text.len());
+    result.push_str(&text[..self.range.start]);
+    result.push_str(&self.new_text);
+    result.push_str(&text[self.range.end..]);
+    result
+  }
+}
+
+/// Formats only the top-level instructions overlapping `range` (a byte range
+/// of `text`), along with the comments directly above them. Everything outside
+/// those instructions is left byte-identical.
+///
+/// Returns the minimal edit to apply to `text`, or `None` if the selection is
+/// already formatted.
+pub fn format_range(text: &str, range: Range<usize>, config: &Configuration) -> Result<Option<TextEdit>, FormatError> {
+  let bom_len = text.len() - strip_bom(text).len();
+  let text = strip_bom(text);
+  let range = range.start.saturating_sub(bom_len).min(text.len())..range.end.saturating_sub(bom_len).min(text.len());
+  let node = parse_node(text)?;
+  if is_file_ignored(&node, text, config) {
+    return Ok(None);
+  }
+
+  let Some(region) = selected_region(&node, text, range) else {
+    return Ok(None);
+  };
+  let formatted = dprint_core::formatting::format(|| generate_range(&node, text, region.clone(), config), config_to_print_options(text, config));
+  // the region never includes the newline ending its last line, so drop the one
+  // the generator emits after the last node
+  let formatted = formatted.strip_suffix('\n').unwrap_or(&formatted);
+  let formatted = formatted.strip_suffix('\r').unwrap_or(formatted);
+
+  Ok(minimal_edit(&text[region.clone()], formatted, region.start).map(|edit| TextEdit {
+    range: edit.range.start + bom_len..edit.range.end + bom_len,
+    new_text: edit.new_text,
+  }))
+}
+
+/// Determines the byte range of whole top-level items (instructions and
+/// comments) to reformat for a selection. The range starts at the beginning of
+/// the first item's line and ends at the end of the last item's line, excluding
+/// the newline.
+fn selected_region(file: &Dockerfile, text: &str, range: Range<usize>) -> Option<Range<usize>> {
+  let items = top_level_items(file, text);
+  let overlaps = |span: &Span| {
+    if range.is_empty() {
+      span.start <= range.start && range.start <= span.end
+    } else {
+      span.start < range.end && range.start < span.end
+    }
+  };
+  let mut first = items.iter().position(|(span, _)| overlaps(span))?;
+  let last = items.iter().rposition(|(span, _)| overlaps(span))?;
+
+  // keep the comments directly above the first instruction with it
+  while first > 0 {
+    let (previous, is_comment) = items[first - 1];
+    let between = &text[previous.end..items[first].0.start];
+    if !is_comment || between.chars().filter(|c| *c == '\n').count() > 1 {
+      break;
+    }
+    first -= 1;
+  }
+
+  let start = items[first].0.start;
+  let start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+  let end = items[last].0.end;
+  let end = end + (text[end..].len() - text[end..].trim_start_matches([' ', '\t']).len());
+  Some(start..end)
+}
+
+/// The spans of the file's top-level instructions interleaved with the spans of
+/// the comments between them, flagged with whether each is a comment.
+fn top_level_items(file: &Dockerfile, text: &str) -> Vec<(Span, bool)> {
+  let mut items = Vec::new();
+  let mut last_pos = 0;
+  for instruction in &file.instructions {
+    let span = instruction.span();
+    items.extend(parse_comments(&text[last_pos..span.start], last_pos).into_iter().map(|c| (c.span, true)));
+    items.push((span, false));
+    last_pos = span.end;
+  }
+  items.extend(parse_comments(&text[last_pos..], last_pos).into_iter().map(|c| (c.span, true)));
+  items
+}
+
+/// Trims the common prefix and suffix of the old and new text so the edit only
+/// covers what actually changed. `offset` is where `old` starts in the file.
+fn minimal_edit(old: &str, new: &str, offset: usize) -> Option<TextEdit> {
+  if old == new {
+    return None;
+  }
+  let prefix = old
+    .char_indices()
+    .zip(new.chars())
+    .find(|((_, a), b)| a != b)
+    .map(|((i, _), _)| i)
+    .unwrap_or(old.len().min(new.len()));
+  let old_rest = &old[prefix..];
+  let new_rest = &new[prefix..];
+  let suffix = old_rest
+    .char_indices()
+    .rev()
+    .zip(new_rest.chars().rev())
+    .take_while(|((_, a), b)| a == b)
+    .last()
+    .map(|((i, _), _)| old_rest.len() - i)
+    .unwrap_or(0);
+  Some(TextEdit {
+    range: offset + prefix..offset + old.len() - suffix,
+    new_text: new_rest[..new_rest.len() - suffix].to_string(),
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::configuration::ConfigurationBuilder;
+
+  fn format_selection(text: &str, selected: &str) -> String {
+    let start = text.find(selected).unwrap();
+    let config = ConfigurationBuilder::new().build();
+    match format_range(text, start..start + selected.len(), &config).unwrap() {
+      Some(edit) => edit.apply(text),
+      None => text.to_string(),
+    }
+  }
+
+  #[test]
+  fn formats_only_overlapping_instructions() {
+    let text = "FROM    a\nRUN    echo  hi\nCMD    b\n";
+    assert_eq!(format_selection(text, "echo"), "FROM    a\nRUN echo hi\nCMD    b\n");
+    assert_eq!(format_selection(text, "a\nRUN"), "FROM a\nRUN echo hi\nCMD    b\n");
+  }
+
+  #[test]
+  fn includes_attached_comments() {
+    let text = "FROM    a\n\n#    note\nRUN    echo\n";
+    assert_eq!(format_selection(text, "echo"), "FROM    a\n\n# note\nRUN echo\n");
+    // a blank line detaches the comment
+    let text = "#    note\n\nRUN    echo\n";
+    assert_eq!(format_selection(text, "echo"), "#    note\n\nRUN echo\n");
+  }
+
+  #[test]
+  fn returns_minimal_edit() {
+    let text = "FROM a\nRUN    echo\nCMD b\n";
+    let start = text.find("echo").unwrap();
+    let edit = format_range(text, start..start, &ConfigurationBuilder::new().build()).unwrap().unwrap();
+    assert_eq!(
+      edit,
+      TextEdit {
+        range: 11..14,
+        new_text: String::new()
+      }
+    );
+  }
+
+  #[test]
+  fn no_edit_when_already_formatted_or_nothing_selected() {
+    let config = ConfigurationBuilder::new().build();
+    assert_eq!(format_range("FROM a\nRUN echo\n", 0..4, &config).unwrap(), None);
+    assert_eq!(format_range("FROM a\n\n\nRUN    echo\n", 7..8, &config).unwrap(), None);
+    assert_eq!(format_range("# dprint-ignore-file\nRUN    echo\n", 21..24, &config).unwrap(), None);
+  }
+
+  #[test]
+  fn keeps_multi_line_instructions_and_heredocs_whole() {
+    let text = "RUN    a \\\n    &&   b\nRUN <<EOF\n  body\nEOF\n";
+    assert_eq!(format_selection(text, "b\n"), "RUN a \\\n    && b\nRUN <<EOF\n  body\nEOF\n");
+    let text = "FROM x\nRUN    <<EOF\n  body\nEOF\n";
+    assert_eq!(format_selection(text, "body"), "FROM x\nRUN <<EOF\n  body\nEOF\n");
+  }
+}
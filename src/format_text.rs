@@ -4,22 +4,68 @@ use std::path::Path;
 
 use crate::ast::Dockerfile;
 use crate::configuration::Configuration;
+use crate::configuration::FormatOptions;
+use crate::configuration::is_dockerfile_name;
 use crate::error::FormatError;
 use crate::generation::generate;
+use crate::generation::is_file_ignored;
+use crate::semantics::verify_semantics;
 
+/// Formats `text` with a configuration resolved by dprint. The path is part of
+/// dprint's interface and doesn't change the result. Returns `None` if it's
+/// already formatted.
 pub fn format_text(_file_path: &Path, text: &str, config: &Configuration) -> Result<Option<String>, FormatError> {
   let result = format_inner(text, config)?;
   if result == text { Ok(None) } else { Ok(Some(result)) }
 }
 
+/// Formats `text` with `options`. Returns `None` if it's already formatted.
+pub fn format_str(text: &str, options: &FormatOptions) -> Result<Option<String>, FormatError> {
+  let result = format_inner(text, &Configuration::from(options.clone()))?;
+  if result == text { Ok(None) } else { Ok(Some(result)) }
+}
+
+/// Reads and formats the file at `path` with `options`, without writing it
+/// back. The file has to be named like a Dockerfile, as decided by
+/// [`is_dockerfile_name`]. The kind of file doesn't pick any defaults:
+/// `Dockerfile`, `Containerfile`, `*.dockerfile` and `*.containerfile` files
+/// are all formatted with `options` alone. Returns `None` if it's already
+/// formatted.
+pub fn format_file(path: &Path, options: &FormatOptions) -> Result<Option<String>, FormatError> {
+  if !is_dockerfile_name(path) {
+    return Err(FormatError::UnrecognizedFileName(path.to_path_buf()));
+  }
+  let text = std::fs::read_to_string(path).map_err(|source| FormatError::Io {
+    path: path.to_path_buf(),
+    source,
+  })?;
+  format_str(&text, options)
+}
+
+/// Formats an already parsed Dockerfile, such as one kept up to date in an
+/// editor with [`Dockerfile::reparse`]. Returns `None` if it's already
+/// formatted.
+pub fn format_dockerfile(file: &Dockerfile, config: &Configuration) -> Option<String> {
+  let result = format_node(file, &file.content, config);
+  if result == file.content { None } else { Some(result) }
+}
+
 fn format_inner(text: &str, config: &Configuration) -> Result<String, FormatError> {
   let text = strip_bom(text);
   let node = parse_node(text)?;
+  let result = format_node(&node, text, config);
+  if config.verify_semantics {
+    verify_semantics(&node, &result, config)?;
+  }
+
+  Ok(result)
+}
 
-  Ok(dprint_core::formatting::format(
-    || generate(&node, text, config),
-    config_to_print_options(text, config),
-  ))
+fn format_node(node: &Dockerfile, text: &str, config: &Configuration) -> String {
+  if is_file_ignored(node, text, config) {
+    return text.to_string();
+  }
+  dprint_core::formatting::format(|| generate(node, text, config), config_to_print_options(text, config))
 }
 
 #[cfg(feature = "tracing")]
@@ -29,15 +75,15 @@ pub fn trace_file(_file_path: &Path, text: &str, config: &Configuration) -> dpri
   dprint_core::formatting::trace_printing(|| generate(&node, text, config), config_to_print_options(text, config))
 }
 
-fn parse_node(text: &str) -> Result<Dockerfile, FormatError> {
+pub(crate) fn parse_node(text: &str) -> Result<Dockerfile, FormatError> {
   Ok(Dockerfile::parse(text)?)
 }
 
-fn strip_bom(text: &str) -> &str {
+pub(crate) fn strip_bom(text: &str) -> &str {
   text.strip_prefix("\u{FEFF}").unwrap_or(text)
 }
 
-fn config_to_print_options(text: &str, config: &Configuration) -> PrintOptions {
+pub(crate) fn config_to_print_options(text: &str, config: &Configuration) -> PrintOptions {
   PrintOptions {
     indent_width: 1,
     max_width: config.line_width,
@@ -75,4 +121,34 @@ mod test {
     );
     assert!(result.is_ok());
   }
+
+  #[test]
+  fn formats_str_with_options() {
+    let options = FormatOptions {
+      cmd_form: crate::configuration::CommandForm::Exec,
+      ..Default::default()
+    };
+    assert_eq!(format_str("cmd node app.js", &options).unwrap().unwrap(), "CMD [\"node\", \"app.js\"]\n");
+    assert_eq!(format_str("FROM alpine\n", &options).unwrap(), None);
+  }
+
+  #[test]
+  fn formats_files_by_name() {
+    let dir = std::env::temp_dir().join(format!("dprint-dockerfile-format-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["Containerfile", "web.Dockerfile", "Dockerfile.dev", "notes.txt"] {
+      std::fs::write(dir.join(name), "from   alpine\ncmd node app.js\n").unwrap();
+    }
+    let options = FormatOptions {
+      cmd_form: crate::configuration::CommandForm::Exec,
+      ..Default::default()
+    };
+    let results = ["Containerfile", "web.Dockerfile", "Dockerfile.dev", "notes.txt", "missing.dockerfile"].map(|name| format_file(&dir.join(name), &options));
+    std::fs::remove_dir_all(&dir).unwrap();
+    for result in &results[..3] {
+      assert_eq!(result.as_ref().unwrap().as_deref(), Some("FROM alpine\nCMD [\"node\", \"app.js\"]\n"));
+    }
+    assert!(matches!(results[3], Err(FormatError::UnrecognizedFileName(_))));
+    assert!(matches!(results[4], Err(FormatError::Io { .. })));
+  }
 }
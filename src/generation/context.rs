@@ -7,6 +7,10 @@ use crate::ast::Span;
 use super::helpers::Node;
 use super::helpers::parse_comments;
 use crate::configuration::Configuration;
+use crate::shell::ShellToken;
+
+/// The width of `ONBUILD `.
+const ONBUILD_PREFIX_WIDTH: u32 = 8;
 
 pub struct Context<'a> {
   pub config: &'a Configuration,
@@ -19,9 +23,8 @@ pub struct Context<'a> {
   /// whether the current breakable string is shell content whose insignificant
   /// whitespace runs should be collapsed
   pub collapse_shell_ws: bool,
-  /// the quote currently open while collapsing shell whitespace (carried across
-  /// the breakable string's components), or `None` when outside a quote
-  pub shell_quote: Option<char>,
+  /// the tokens of the shell command whose whitespace is being collapsed
+  pub shell_tokens: Vec<ShellToken>,
 }
 
 impl<'a> Context<'a> {
@@ -35,7 +38,7 @@ impl<'a> Context<'a> {
       parent_stack: Vec::new(),
       gen_string_content: false,
       collapse_shell_ws: false,
-      shell_quote: None,
+      shell_tokens: Vec::new(),
     }
   }
 
@@ -63,6 +66,13 @@ impl<'a> Context<'a> {
     self.parent_stack.last()
   }
 
+  /// The width of the `ONBUILD ` prefixes printed before the current
+  /// instruction, which its continuation lines are indented past.
+  pub fn onbuild_prefix_width(&self) -> u32 {
+    let onbuild_count = self.parent_stack.iter().filter(|node| matches!(node, Node::Onbuild(_))).count();
+    onbuild_count as u32 * ONBUILD_PREFIX_WIDTH
+  }
+
   /// Interleaves the given nodes with the comments found in the text between
   /// them. When `include_leading` is false, comments appearing before the first
   /// node are skipped so they don't end up on the instruction's prefix line; the
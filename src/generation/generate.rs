@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use dprint_core::formatting::ir_helpers::SingleLineOptions;
 use dprint_core::formatting::ir_helpers::gen_from_raw_string;
 use dprint_core::formatting::*;
@@ -5,25 +7,64 @@ use dprint_core_macros::sc;
 
 use super::context::Context;
 use super::helpers::*;
+use super::shell_format::ShellLine;
+use super::shell_format::format_shell_lines;
 use crate::ast::*;
+use crate::configuration::CommandForm;
 use crate::configuration::Configuration;
+use crate::configuration::KeyValueLayout;
+use crate::configuration::QuoteStyle;
+use crate::exec_form::exec_form_arguments;
+use crate::exec_form::json_string;
+use crate::flag_order::sort_flags;
+use crate::healthcheck::check_option;
+use crate::quoting::requote_value;
+use crate::shell::ShellToken;
+use crate::shell::ShellTokenKind;
+use crate::shell::tokenize;
 
 pub fn generate(file: &Dockerfile, text: &str, config: &Configuration) -> PrintItems {
+  generate_range(file, text, 0..text.len(), config)
+}
+
+/// Generates only the top-level instructions and comments that lie entirely
+/// within `range` (a byte range of `text`), e.g. for formatting a selection.
+pub fn generate_range(file: &Dockerfile, text: &str, range: Range<usize>, config: &Configuration) -> PrintItems {
   let mut context = Context::new(text, file, config);
   let mut items = PrintItems::new();
-  let top_level_nodes = context.gen_nodes_with_comments(0, text.len(), true, file.instructions.iter().map(|i| i.into()));
+  let directives = file.directives.iter().map(Node::from);
+  let instructions = file.instructions.iter().map(Node::from);
+  let nodes = directives
+    .chain(instructions)
+    .filter(|node| node.span().start >= range.start && node.span().end <= range.end);
+  let top_level_nodes = context.gen_nodes_with_comments(range.start, range.end, true, nodes);
+  let stage_starts = if config.blank_line_between_stages {
+    stage_block_starts(file, &top_level_nodes, text)
+  } else {
+    vec![false; top_level_nodes.len()]
+  };
 
   for (i, node) in top_level_nodes.iter().enumerate() {
-    let node_items = gen_node(node.clone(), &mut context);
-    // safety net: never drop a comment. some instructions discard comments that
-    // follow a line continuation (the parser's arg_ws consumes them); recover
-    // any that weren't emitted and place them just before the instruction.
-    items.extend(recover_dropped_comments(node, &mut context));
-    items.extend(node_items);
+    let is_ignored = i > 0 && !node.is_comment() && top_level_nodes[i - 1].is_ignore_comment(&config.ignore_node_comment_text);
+    if is_ignored {
+      // printed exactly as written, including any comments inside it
+      items.extend(gen_from_raw_string(context.span_text(&node.span())));
+    } else {
+      let node_items = gen_node(node.clone(), &mut context);
+      // safety net: never drop a comment. some instructions discard comments that
+      // follow a line continuation (the parser's arg_ws consumes them); recover
+      // any that weren't emitted and place them just before the instruction.
+      items.extend(recover_dropped_comments(node, &mut context));
+      items.extend(node_items);
+    }
     items.push_signal(Signal::NewLine);
     if let Some(next_node) = top_level_nodes.get(i + 1) {
-      let text_between = &text[node.span().end..next_node.span().start];
-      if text_between.chars().filter(|c| *c == '\n').count() > 1 {
+      let blank_lines = if stage_starts[i + 1] {
+        1
+      } else {
+        blank_lines_between(text, node, next_node).min(config.max_consecutive_blank_lines as usize)
+      };
+      for _ in 0..blank_lines {
         items.push_signal(Signal::NewLine);
       }
     }
@@ -32,6 +73,40 @@ pub fn generate(file: &Dockerfile, text: &str, config: &Configuration) -> PrintI
   items
 }
 
+/// Whether the file opts out of formatting with an ignore-file comment among
+/// the comments at its top, before the first instruction.
+pub fn is_file_ignored(file: &Dockerfile, text: &str, config: &Configuration) -> bool {
+  let header_end = file.instructions.first().map(|instruction| instruction.span().start).unwrap_or(text.len());
+  parse_comments(&text[..header_end], 0)
+    .iter()
+    .any(|comment| is_ignore_comment(&comment.content, &config.ignore_file_comment_text))
+}
+
+/// The number of blank lines in the source between two top-level nodes.
+fn blank_lines_between(text: &str, node: &Node, next_node: &Node) -> usize {
+  let text_between = &text[node.span().end..next_node.span().start];
+  text_between.chars().filter(|c| *c == '\n').count().saturating_sub(1)
+}
+
+/// Flags the top-level nodes that begin a build stage other than the file's
+/// first: a `FROM`, or the comment block directly above it (no blank lines in
+/// between) so the comments stay attached to the stage.
+fn stage_block_starts(file: &Dockerfile, nodes: &[Node], text: &str) -> Vec<bool> {
+  let mut starts = vec![false; nodes.len()];
+  let first_from = file.instructions.iter().find(|i| matches!(i, Instruction::From(_))).map(|i| i.span());
+  for (i, node) in nodes.iter().enumerate() {
+    if !matches!(node, Node::From(_)) || Some(node.span()) == first_from {
+      continue;
+    }
+    let mut start = i;
+    while start > 0 && nodes[start - 1].is_comment() && blank_lines_between(text, &nodes[start - 1], &nodes[start]) == 0 {
+      start -= 1;
+    }
+    starts[start] = true;
+  }
+  starts
+}
+
 fn gen_node<'a>(node: Node<'a>, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
 
@@ -39,7 +114,8 @@ fn gen_node<'a>(node: Node<'a>, context: &mut Context<'a>) -> PrintItems {
   items.extend(match node {
     Node::Arg(node) => gen_arg_instruction(node, context),
     Node::Cmd(node) => gen_cmd_instruction(node, context),
-    Node::Copy(node) => gen_copy_instruction(node, context),
+    Node::Copy(node) => gen_copy_instruction("COPY", node.span, &node.flags, &node.args, context),
+    Node::Add(node) => gen_copy_instruction("ADD", node.span, &node.flags, &node.args, context),
     Node::Entrypoint(node) => gen_entrypoint_instruction(node, context),
     Node::Env(node) => gen_env_instruction(node, context),
     Node::EnvVar(node) => gen_env_var(node, context),
@@ -48,6 +124,7 @@ fn gen_node<'a>(node: Node<'a>, context: &mut Context<'a>) -> PrintItems {
     Node::Label(node) => gen_label_instruction(node, context),
     Node::LabelLabel(node) => gen_label(node, context),
     Node::Misc(node) => gen_misc_instruction(node, context),
+    Node::ParserDirective(node) => gen_parser_directive(node, context),
     Node::Shell(node) => gen_shell_instruction(node, context),
     Node::Onbuild(node) => gen_onbuild_instruction(node, context),
     Node::Healthcheck(node) => gen_healthcheck_instruction(node, context),
@@ -72,7 +149,7 @@ fn gen_arg_instruction<'a>(node: &'a ArgInstruction, context: &mut Context<'a>)
 
   if let Some(value) = &node.value {
     items.push_sc(sc!("="));
-    items.extend(gen_node(value.into(), context));
+    items.extend(gen_value(value.span, context).unwrap_or_else(|| gen_node(value.into(), context)));
   }
 
   items
@@ -83,44 +160,50 @@ fn gen_cmd_instruction<'a>(node: &'a CmdInstruction, context: &mut Context<'a>)
   items.push_sc(sc!("CMD "));
   items.extend(match &node.expr {
     ShellOrExecExpr::Exec(node) => gen_node(node.into(), context),
-    ShellOrExecExpr::Shell(node) => gen_node(node.into(), context),
+    ShellOrExecExpr::Shell(node) => gen_command_in_form(node, context.config.cmd_form, true, context),
   });
   items
 }
 
-fn gen_copy_instruction<'a>(node: &'a CopyInstruction, context: &mut Context<'a>) -> PrintItems {
+/// Generates a `COPY` or `ADD` instruction, which take the same arguments.
+fn gen_copy_instruction<'a>(keyword: &'static str, span: Span, flags: &'a [CopyFlag], args: &'a CopyArgs, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
-  let prefix = sc!("COPY ");
-  items.push_sc(prefix);
+  items.push_string(format!("{} ", keyword));
 
-  match &node.args {
+  match args {
     CopyArgs::Exec(array) => {
-      for flag in &node.flags {
+      for flag in sorted_flags(keyword, flags, |flag| &flag.name, context) {
         items.extend(gen_node(flag.into(), context));
         items.push_sc(sc!(" "));
       }
       items.extend(gen_node(array.into(), context));
     }
     CopyArgs::Paths { sources, destination } => {
-      let value_nodes = node
-        .flags
+      let value_nodes = flags
         .iter()
         .map(|flag| flag.into())
         .chain(sources.iter().map(|source| source.into()))
         .chain(std::iter::once(destination.into()));
-      let nodes = context.gen_nodes_with_comments(node.span.start, node.span.end, false, value_nodes);
+      let mut nodes = context.gen_nodes_with_comments(span.start, span.end, false, value_nodes);
+      let indent_width = keyword.len() as u32 + 1 + context.onbuild_prefix_width();
+      let has_comments = nodes.iter().any(|node| node.is_comment());
+      if !has_comments {
+        // flags with comments between them stay where they're written
+        for (flag_node, flag) in nodes.iter_mut().zip(sorted_flags(keyword, flags, |flag| &flag.name, context)) {
+          *flag_node = flag.into();
+        }
+      }
 
-      if nodes.iter().any(|node| node.is_comment()) {
-        // preserve comments by breaking onto multiple lines, aligned with the arguments
-        items.extend(gen_multi_line_items(nodes, prefix.text.chars().count() as u32, context));
+      // the arguments break one per line, aligned with the first, when they
+      // exceed the line width or there are comments between them
+      if context.config.copy_destination_new_line && nodes.len() > 1 && !has_comments {
+        let destination = nodes.pop().unwrap();
+        items.extend(gen_multi_line_items(nodes, indent_width, KeyValueLayout::FitOrBreakAll, context));
+        items.push_sc(space_continuation(context.escape()));
+        items.push_signal(Signal::NewLine);
+        items.extend(ir_helpers::with_indent_times(gen_node(destination, context), indent_width));
       } else {
-        // keep everything on a single line
-        for (i, node) in nodes.into_iter().enumerate() {
-          if i > 0 {
-            items.push_sc(sc!(" "));
-          }
-          items.extend(gen_node(node, context));
-        }
+        items.extend(gen_multi_line_items(nodes, indent_width, KeyValueLayout::FitOrBreakAll, context));
       }
     }
   }
@@ -132,32 +215,82 @@ fn gen_entrypoint_instruction<'a>(node: &'a EntrypointInstruction, context: &mut
   items.push_sc(sc!("ENTRYPOINT "));
   items.extend(match &node.expr {
     ShellOrExecExpr::Exec(node) => gen_node(node.into(), context),
-    ShellOrExecExpr::Shell(node) => gen_node(node.into(), context),
+    ShellOrExecExpr::Shell(node) => gen_command_in_form(node, context.config.entrypoint_form, false, context),
   });
   items
 }
 
+/// Generates a shell-form `CMD` or `ENTRYPOINT` command, rewritten as an
+/// exec-form array when `form` is `exec` and the command doesn't need a shell.
+/// Commands nested in `ONBUILD` or `HEALTHCHECK` keep their form, since the
+/// image they run in isn't known.
+fn gen_command_in_form<'a>(node: &'a BreakableString, form: CommandForm, is_cmd: bool, context: &mut Context<'a>) -> PrintItems {
+  if form == CommandForm::Exec
+    && context.parent().is_none()
+    && let Ok(arguments) = exec_form_arguments(context.dockerfile, node, is_cmd)
+  {
+    let elements = arguments.iter().map(|argument| json_string(argument)).collect::<Vec<_>>();
+    let mut items = PrintItems::new();
+    items.push_string(format!("[{}]", elements.join(", ")));
+    return items;
+  }
+  gen_node(node.into(), context)
+}
+
 fn gen_env_instruction<'a>(node: &'a EnvInstruction, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   let nodes = context.gen_nodes_with_comments(node.span.start, node.span.end, false, node.vars.iter().map(|i| i.into()));
   let prefix = sc!("ENV ");
   items.push_sc(prefix);
-  items.extend(gen_multi_line_items(nodes, prefix.text.chars().count() as u32, context));
+  items.extend(gen_multi_line_items(
+    nodes,
+    prefix.text.chars().count() as u32 + context.onbuild_prefix_width(),
+    context.config.key_value_layout,
+    context,
+  ));
   items
 }
 
+// the `=` of a pair is never padded to line up with other pairs: Docker splits
+// pairs at whitespace, so `KEY  =value` would be read as the legacy `ENV KEY
+// value` form (or fail to parse as a later pair), and `KEY=  value` would
+// leave `value` without a key. the same goes for `LABEL`
 fn gen_env_var<'a>(node: &'a EnvVar, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   items.extend(gen_node((&node.key).into(), context));
   items.push_sc(sc!("="));
-  items.extend(gen_node((&node.value).into(), context));
+  let value = match node.value.components.as_slice() {
+    // values with spaces are double-quoted even when maintaining the quotes
+    [BreakableStringComponent::String(value)] if context.config.quote_style == QuoteStyle::Maintain && context.span_text(&value.span).contains(' ') => {
+      requote_value(context.span_text(&value.span), context.escape(), QuoteStyle::PreferDouble).map(|text| gen_from_raw_string(&text))
+    }
+    [BreakableStringComponent::String(value)] => gen_value(value.span, context),
+    _ => None,
+  };
+  items.extend(value.unwrap_or_else(|| gen_node((&node.value).into(), context)));
   items
 }
 
+/// Generates an `ENV`, `LABEL` or `ARG` value in the configured `quoteStyle`,
+/// or returns `None` to print it as usual.
+fn gen_value<'a>(span: Span, context: &mut Context<'a>) -> Option<PrintItems> {
+  let text = requote_value(context.span_text(&span), context.escape(), context.config.quote_style)?;
+  Some(gen_from_raw_string(&text))
+}
+
+/// The flags of a `keyword` instruction in the order to print them in, which is
+/// the canonical one when `sortFlags` is set and no flag is repeated.
+fn sorted_flags<'a, T>(keyword: &str, flags: &'a [T], name: impl Fn(&T) -> &SpannedString, context: &Context) -> Vec<&'a T> {
+  match sort_flags(keyword, flags, name) {
+    Ok(sorted) if context.config.sort_flags => sorted,
+    _ => flags.iter().collect(),
+  }
+}
+
 fn gen_from_instruction<'a>(node: &'a FromInstruction, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   items.push_sc(sc!("FROM "));
-  for flag in &node.flags {
+  for flag in sorted_flags("FROM", &node.flags, |flag| &flag.name, context) {
     items.extend(gen_node(flag.into(), context));
     items.push_sc(sc!(" "));
   }
@@ -184,7 +317,12 @@ fn gen_label_instruction<'a>(node: &'a LabelInstruction, context: &mut Context<'
   items.push_sc(prefix);
   // route through gen_nodes_with_comments so comments between labels are kept
   let nodes = context.gen_nodes_with_comments(node.span.start, node.span.end, false, node.labels.iter().map(|l| l.into()));
-  items.extend(gen_multi_line_items(nodes, prefix.text.chars().count() as u32, context));
+  items.extend(gen_multi_line_items(
+    nodes,
+    prefix.text.chars().count() as u32 + context.onbuild_prefix_width(),
+    context.config.key_value_layout,
+    context,
+  ));
   items
 }
 
@@ -192,21 +330,29 @@ fn gen_label<'a>(node: &'a Label, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   items.extend(gen_node((&node.name).into(), context));
   items.push_sc(sc!("="));
-  items.extend(gen_node((&node.value).into(), context));
+  items.extend(gen_value(node.value.span, context).unwrap_or_else(|| gen_node((&node.value).into(), context)));
   items
 }
 
-fn gen_multi_line_items<'a>(nodes: Vec<Node<'a>>, indent_width: u32, context: &mut Context<'a>) -> PrintItems {
+/// Generates values that sit on one line when they fit and otherwise break
+/// onto indented continuation lines, laid out as `layout` says. Values with
+/// comments between them are always on separate lines.
+fn gen_multi_line_items<'a>(nodes: Vec<Node<'a>>, indent_width: u32, layout: KeyValueLayout, context: &mut Context<'a>) -> PrintItems {
   let count = nodes.len();
   let nodes_with_line_index = nodes
     .into_iter()
     .map(|node| {
-      let (line_index, _) = node.span().relative_span(context.dockerfile);
+      let (line_index, _) = context.dockerfile.line_col(node.span().start);
       (node, line_index)
     })
     .collect::<Vec<_>>();
   let force_use_new_lines = nodes_with_line_index.len() > 1
-    && (nodes_with_line_index[0].1 < nodes_with_line_index[1].1 || nodes_with_line_index.iter().any(|(node, _)| node.is_comment()));
+    && (nodes_with_line_index.iter().any(|(node, _)| node.is_comment())
+      || match layout {
+        KeyValueLayout::Maintain => nodes_with_line_index[0].1 < nodes_with_line_index[1].1,
+        KeyValueLayout::FitOrBreakAll => false,
+        KeyValueLayout::OnePerLine => true,
+      });
   let space_continuation = space_continuation(context.escape());
 
   ir_helpers::gen_separated_values(
@@ -261,8 +407,21 @@ fn gen_multi_line_items<'a>(nodes: Vec<Node<'a>>, indent_width: u32, context: &m
 fn gen_misc_instruction<'a>(node: &'a MiscInstruction, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   items.extend(gen_node((&node.instruction).into(), context));
-  items.push_sc(sc!(" "));
-  items.extend(gen_node((&node.arguments).into(), context));
+  // an `ONBUILD` trigger can be a bare keyword
+  if !node.arguments.components.is_empty() {
+    items.push_sc(sc!(" "));
+    items.extend(gen_node((&node.arguments).into(), context));
+  }
+  items
+}
+
+fn gen_parser_directive<'a>(node: &'a ParserDirective, _context: &mut Context<'a>) -> PrintItems {
+  // BuildKit's canonical form: a lowercase name with no spaces around the `=`
+  let mut items = PrintItems::new();
+  items.push_sc(sc!("# "));
+  items.extend(gen_from_raw_string(&node.name.content));
+  items.push_sc(sc!("="));
+  items.extend(gen_from_raw_string(&node.value.content));
   items
 }
 
@@ -272,12 +431,65 @@ fn gen_run_instruction<'a>(node: &'a RunInstruction, context: &mut Context<'a>)
   items.push_sc(sc!("RUN "));
   items.extend(match &node.expr {
     ShellOrExecExpr::Exec(node) => gen_node(node.into(), context),
-    ShellOrExecExpr::Shell(node) => gen_node(node.into(), context),
+    ShellOrExecExpr::Shell(node) => match gen_formatted_shell(node, context) {
+      Some(formatted) => formatted,
+      None => gen_node(node.into(), context),
+    },
   });
 
   items
 }
 
+/// The indentation of a formatted shell command's continuation lines, which
+/// lines them up with the command after `RUN ` (and past any `ONBUILD `).
+const SHELL_INDENT_WIDTH: usize = 4;
+/// The extra indentation of each block a shell command line is nested in.
+const SHELL_BLOCK_INDENT_WIDTH: usize = 2;
+
+/// Generates a shell command from its parsed lines when `shellFormatting` is
+/// enabled, indenting continuation lines past `RUN ` and by their block depth.
+/// Returns `None` to print the command as usual.
+fn gen_formatted_shell<'a>(node: &'a BreakableString, context: &mut Context<'a>) -> Option<PrintItems> {
+  if !context.config.shell_formatting || context.escape() != '\\' {
+    return None;
+  }
+  let lines = format_shell_lines(node)?;
+  let indent_width = SHELL_INDENT_WIDTH + context.onbuild_prefix_width() as usize;
+  let mut items = PrintItems::new();
+  if matches!(lines.first(), Some(ShellLine::Comment { .. })) {
+    items.push_sc(continuation(context.escape()));
+    items.push_signal(Signal::NewLine);
+  }
+  for (i, line) in lines.iter().enumerate() {
+    let is_last = i == lines.len() - 1;
+    match line {
+      ShellLine::Code { depth, tokens } => {
+        if i > 0 {
+          items.push_string(" ".repeat(indent_width + depth * SHELL_BLOCK_INDENT_WIDTH));
+        }
+        for (space, text) in tokens {
+          if *space {
+            items.push_space();
+          }
+          // via gen_from_raw_string so a tab in a quoted word becomes a Tab signal
+          items.extend(gen_from_raw_string(text));
+        }
+        if !is_last {
+          items.push_sc(space_continuation(context.escape()));
+        }
+      }
+      ShellLine::Comment { depth, component } => {
+        items.push_string(" ".repeat(indent_width + depth * SHELL_BLOCK_INDENT_WIDTH));
+        items.extend(gen_node((*component).into(), context));
+      }
+    }
+    if !is_last {
+      items.push_signal(Signal::NewLine);
+    }
+  }
+  Some(items)
+}
+
 fn gen_shell_instruction<'a>(node: &'a ShellInstruction, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
   items.push_sc(sc!("SHELL "));
@@ -306,11 +518,14 @@ fn gen_healthcheck_instruction<'a>(node: &'a HealthcheckInstruction, context: &m
   // the options render inline as the group's first value, each with a leading
   // space so they separate from the keyword (an empty value when there are none)
   let mut flags_items = PrintItems::new();
-  for flag in &node.flags {
+  for flag in sorted_flags("HEALTHCHECK", &node.flags, |flag| &flag.name, context) {
     flags_items.push_sc(sc!(" --"));
     flags_items.extend(gen_node((&flag.name).into(), context));
     flags_items.push_sc(sc!("="));
-    flags_items.extend(gen_node((&flag.value).into(), context));
+    match check_option(&flag.name.content, &flag.value.content) {
+      Ok(value) if context.config.normalize_healthcheck_options => flags_items.extend(gen_from_raw_string(&value)),
+      _ => flags_items.extend(gen_node((&flag.value).into(), context)),
+    }
   }
   let command_items = match &node.cmd {
     Some(instruction) => gen_node((&**instruction).into(), context),
@@ -325,14 +540,14 @@ fn gen_healthcheck_instruction<'a>(node: &'a HealthcheckInstruction, context: &m
   // fit under the line width and otherwise break onto an aligned continuation
   // line (#29). a break the author already wrote forces the multi-line form, as
   // does `healthcheckCmdNewLine` when there's a command preceded by options.
-  let first_line = node.span.relative_span(context.dockerfile).0;
+  let first_line = context.dockerfile.line_col(node.span.start).0;
   let command_span = node.cmd.as_ref().map(|c| c.span()).unwrap_or(node.span);
-  let command_line = command_span.relative_span(context.dockerfile).0;
+  let command_line = context.dockerfile.line_col(command_span.start).0;
   let proactive_split = context.config.healthcheck_cmd_new_line && node.cmd.is_some() && !node.flags.is_empty();
   let force_use_new_lines = command_line > first_line || proactive_split;
   items.extend(gen_grouped_values(
     vec![(flags_items, first_line), (command_items, command_line)],
-    HEALTHCHECK_CONTINUATION_INDENT,
+    HEALTHCHECK_CONTINUATION_INDENT + context.onbuild_prefix_width(),
     force_use_new_lines,
     context,
   ));
@@ -441,9 +656,12 @@ fn gen_breakable_string<'a>(node: &'a BreakableString, context: &mut Context<'a>
   // in the quoted env-var form (which preserves its content verbatim)
   let is_shell_command = matches!(context.parent(), Some(Node::Run(_) | Node::Cmd(_) | Node::Entrypoint(_)));
   let previous_collapse = context.collapse_shell_ws;
-  let previous_quote = context.shell_quote;
   context.collapse_shell_ws = is_shell_command && !use_quotes;
-  context.shell_quote = None;
+  let previous_tokens = if context.collapse_shell_ws {
+    std::mem::replace(&mut context.shell_tokens, tokenize(node))
+  } else {
+    Vec::new()
+  };
   let continuation = continuation(context.escape());
   let space_continuation = space_continuation(context.escape());
 
@@ -472,7 +690,7 @@ fn gen_breakable_string<'a>(node: &'a BreakableString, context: &mut Context<'a>
       if let BreakableStringComponent::String(text) = component {
         // when the component ends inside a quote, any trailing whitespace is
         // part of the (kept) string content, so don't add a separator space
-        let ends_in_quote = context.collapse_shell_ws && context.shell_quote.is_some();
+        let ends_in_quote = context.collapse_shell_ws && is_inside_token(&context.shell_tokens, text.span.end);
         if !use_quotes && !ends_in_quote && text.content.ends_with(" ") {
           items.push_sc(space_continuation);
         } else {
@@ -487,8 +705,10 @@ fn gen_breakable_string<'a>(node: &'a BreakableString, context: &mut Context<'a>
   }
 
   context.gen_string_content = previous_gen_string_content;
+  if context.collapse_shell_ws {
+    context.shell_tokens = previous_tokens;
+  }
   context.collapse_shell_ws = previous_collapse;
-  context.shell_quote = previous_quote;
   items
 }
 
@@ -529,16 +749,14 @@ fn gen_string<'a>(node: &'a SpannedString, context: &mut Context<'a>) -> PrintIt
   let raw = context.span_text(&node.span);
 
   if context.collapse_shell_ws {
-    // run the quote-aware collapse on the raw text so significant whitespace
-    // inside a quote is never stripped by a blind trim
-    let collapsed = collapse_shell_whitespace(raw, should_trim, &mut context.shell_quote);
+    let collapsed = collapse_shell_whitespace(raw, node.span, should_trim, &context.shell_tokens);
     // trailing whitespace outside a quote is just a separator (represented by
     // the line-continuation marker), so drop it; inside a quote it is part of
     // the string and must be kept
-    let text = if context.shell_quote.is_none() {
-      collapsed.trim_end()
-    } else {
+    let text = if is_inside_token(&context.shell_tokens, node.span.end) {
       collapsed.as_str()
+    } else {
+      collapsed.trim_end()
     };
     items.extend(gen_from_raw_string(text));
   } else {
@@ -548,77 +766,42 @@ fn gen_string<'a>(node: &'a SpannedString, context: &mut Context<'a>) -> PrintIt
   items
 }
 
-/// Collapses runs of two or more insignificant whitespace characters into a
-/// single space within shell command text, leaving whitespace inside quotes and
-/// after a backslash escape untouched. `quote` tracks the open quote across the
-/// breakable string's components. Leading whitespace is kept as indentation
-/// unless `drop_leading` is set (the leading content line, whose indentation is
-/// trimmed away).
-fn collapse_shell_whitespace(text: &str, drop_leading: bool, quote: &mut Option<char>) -> String {
+/// Collapses runs of two or more whitespace characters between the shell
+/// tokens of one line of a command into a single space, leaving whitespace
+/// inside quotes, escapes and comments untouched. Leading whitespace is kept
+/// as indentation unless `drop_leading` is set (the leading content line, whose
+/// indentation is trimmed away).
+fn collapse_shell_whitespace(text: &str, span: Span, drop_leading: bool, tokens: &[ShellToken]) -> String {
   let mut out = String::with_capacity(text.len());
-  let mut chars = text.chars().peekable();
-
-  // leading whitespace outside a quote is indentation: keep it, or drop it for
-  // the leading content line
-  if quote.is_none() {
-    while matches!(chars.peek(), Some(' ' | '\t')) {
-      let c = chars.next().unwrap();
+  let mut last_end = span.start;
+  let whitespace = tokens
+    .iter()
+    .filter(|token| token.kind == ShellTokenKind::Whitespace && token.span.start >= span.start && token.span.end <= span.end);
+  for token in whitespace {
+    out.push_str(&text[last_end - span.start..token.span.start - span.start]);
+    if token.span.start == span.start {
       if !drop_leading {
-        out.push(c);
-      }
-    }
-  }
-
-  while let Some(c) = chars.next() {
-    match *quote {
-      Some('\'') => {
-        out.push(c);
-        if c == '\'' {
-          *quote = None;
-        }
-      }
-      Some(_) => {
-        // inside a double quote: a backslash escapes the next character
-        out.push(c);
-        if c == '\\' {
-          if let Some(next) = chars.next() {
-            out.push(next);
-          }
-        } else if c == '"' {
-          *quote = None;
-        }
+        out.push_str(&token.text);
       }
-      None => match c {
-        '\'' | '"' => {
-          out.push(c);
-          *quote = Some(c);
-        }
-        // an escaped character (including `\ `) is kept verbatim
-        '\\' => {
-          out.push(c);
-          if let Some(next) = chars.next() {
-            out.push(next);
-          }
-        }
-        ' ' | '\t' => {
-          let mut count = 1;
-          while matches!(chars.peek(), Some(' ' | '\t')) {
-            chars.next();
-            count += 1;
-          }
-          if count > 1 {
-            out.push(' ');
-          } else {
-            out.push(c);
-          }
-        }
-        _ => out.push(c),
-      },
+    } else if token.text.len() > 1 {
+      out.push(' ');
+    } else {
+      out.push_str(&token.text);
     }
+    last_end = token.span.end;
   }
+  out.push_str(&text[last_end - span.start..]);
   out
 }
 
+/// Whether a non-whitespace shell token (ex. a quoted string continued onto
+/// the next line) spans the position.
+fn is_inside_token(tokens: &[ShellToken], position: usize) -> bool {
+  tokens
+    .iter()
+    .any(|token| token.kind != ShellTokenKind::Whitespace && token.span.start < position && position < token.span.end)
+}
+
 fn gen_copy_flag<'a>(node: &'a CopyFlag, context: &mut Context<'a>) -> PrintItems {
   // ex: --from=foo
   let mut items = PrintItems::new();
@@ -646,7 +829,8 @@ fn gen_comment<'a>(comment: &SpannedComment, context: &mut Context<'a>) -> Print
 /// Heredoc bodies are excluded — they are verbatim and may contain `#` lines.
 fn recover_dropped_comments<'a>(node: &Node<'a>, context: &mut Context<'a>) -> PrintItems {
   let mut items = PrintItems::new();
-  if node.is_comment() {
+  // a parser directive is itself a `#` line rather than something containing one
+  if node.is_comment() || matches!(node, Node::ParserDirective(_)) {
     return items;
   }
   let span = match node {
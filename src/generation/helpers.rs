@@ -27,6 +27,7 @@ create_node_ref!(
   Arg(ArgInstruction),
   Cmd(CmdInstruction),
   Copy(CopyInstruction),
+  Add(AddInstruction),
   CopyFlag(CopyFlag),
   From(FromInstruction),
   FromFlag(FromFlag),
@@ -41,6 +42,7 @@ create_node_ref!(
   Healthcheck(HealthcheckInstruction),
   Heredoc(HeredocInstruction),
   Misc(MiscInstruction),
+  ParserDirective(ParserDirective),
   String(SpannedString),
   BreakableString(BreakableString),
   StringArray(StringArray),
@@ -60,6 +62,7 @@ impl<'a> Node<'a> {
       Entrypoint(node) => node.span,
       Cmd(node) => node.span,
       Copy(node) => node.span,
+      Add(node) => node.span,
       CopyFlag(node) => node.span,
       Env(node) => node.span,
       EnvVar(node) => node.span,
@@ -68,6 +71,7 @@ impl<'a> Node<'a> {
       Healthcheck(node) => node.span,
       Heredoc(node) => node.span,
       Misc(node) => node.span,
+      ParserDirective(node) => node.span,
       String(node) => node.span,
       BreakableString(node) => node.span,
       StringArray(node) => node.span,
@@ -79,6 +83,25 @@ impl<'a> Node<'a> {
   pub fn is_comment(&self) -> bool {
     matches!(self, Node::Comment(_) | Node::CommentRc(_))
   }
+
+  /// Whether this is a comment consisting of `ignore_text`, optionally followed
+  /// by an explanation (ex. `# dprint-ignore - aligned by hand`).
+  pub fn is_ignore_comment(&self, ignore_text: &str) -> bool {
+    match self {
+      Node::Comment(comment) => is_ignore_comment(&comment.content, ignore_text),
+      Node::CommentRc(comment) => is_ignore_comment(&comment.content, ignore_text),
+      _ => false,
+    }
+  }
+}
+
+/// Whether a comment's text (including its leading `#`) is `ignore_text`,
+/// optionally followed by whitespace and an explanation.
+pub fn is_ignore_comment(comment_text: &str, ignore_text: &str) -> bool {
+  let body = comment_text.trim_start_matches('#').trim();
+  body
+    .strip_prefix(ignore_text)
+    .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
 }
 
 pub fn parse_comments(text: &str, offset: usize) -> Vec<SpannedComment> {
@@ -126,6 +149,7 @@ impl<'a> From<&'a Instruction> for Node<'a> {
       Entrypoint(node) => node.into(),
       Cmd(node) => node.into(),
       Copy(node) => node.into(),
+      Add(node) => node.into(),
       Env(node) => node.into(),
       Shell(node) => node.into(),
       Onbuild(node) => node.into(),
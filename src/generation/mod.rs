@@ -1,5 +1,7 @@
 mod context;
 mod generate;
 mod helpers;
+mod shell_format;
 
 pub use generate::*;
+pub use helpers::parse_comments;
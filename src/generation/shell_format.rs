@@ -0,0 +1,243 @@
+use crate::ast::BreakableString;
+use crate::ast::BreakableStringComponent;
+use crate::shell::ShellToken;
+use crate::shell::ShellTokenKind;
+use crate::shell::replace_backticks;
+use crate::shell::tokenize;
+
+/// A line of a shell command formatted by [`format_shell_lines`].
+pub(super) enum ShellLine<'a> {
+  /// Shell code nested in `depth` blocks, as the text of each token and
+  /// whether a space goes before it.
+  Code { depth: usize, tokens: Vec<(bool, String)> },
+  /// A Dockerfile comment between continued lines.
+  Comment { depth: usize, component: &'a BreakableStringComponent },
+}
+
+/// Formats each line of a shell command, keeping its line breaks. Returns
+/// `None` when the command can't be reformatted safely (ex. an unterminated
+/// quote, a heredoc or an unbalanced block), in which case it should be printed
+/// as written.
+pub(super) fn format_shell_lines(value: &BreakableString) -> Option<Vec<ShellLine<'_>>> {
+  let tokens = tokenize(value);
+  if !can_format(&tokens) {
+    return None;
+  }
+  let mut state = BlockState::default();
+  let mut lines = Vec::with_capacity(value.components.len());
+  for component in &value.components {
+    match component {
+      BreakableStringComponent::String(text) => {
+        let span = text.span;
+        let line_tokens = tokens.iter().filter(|token| token.span.start >= span.start && token.span.start < span.end);
+        // a token continued onto the next line can't keep its line break
+        let mut pieces = Vec::new();
+        let mut had_space = false;
+        for token in line_tokens {
+          if token.span.end > span.end {
+            return None;
+          }
+          if token.kind == ShellTokenKind::Whitespace {
+            had_space = true;
+          } else {
+            pieces.push((token, had_space));
+            had_space = false;
+          }
+        }
+        lines.push(state.format_line(&pieces)?);
+      }
+      BreakableStringComponent::Comment(_) => lines.push(ShellLine::Comment { depth: state.depth, component }),
+    }
+  }
+  (state.depth == 0 && !state.pending_case).then_some(lines)
+}
+
+fn can_format(tokens: &[ShellToken]) -> bool {
+  tokens.iter().enumerate().all(|(i, token)| {
+    let previous = i.checked_sub(1).map(|i| &tokens[i]);
+    // bash's `|&`, `;&` and `;;&` lex as two operators that can't be separated
+    let is_split_operator = token.text == "&" && previous.is_some_and(|previous| matches!(previous.text.as_str(), "|" | ";" | ";;"));
+    token.terminated && !is_split_operator && !(token.kind == ShellTokenKind::Redirection && token.text.contains("<<"))
+  })
+}
+
+/// Tracks the compound commands (`if`, `for`, `while`, `case` and `{ }`) that
+/// are open at the current position.
+#[derive(Default)]
+struct BlockState {
+  depth: usize,
+  /// Whether the next word is a command name, where keywords are recognized.
+  not_command_position: bool,
+  /// Whether a `case` is waiting for its `in`.
+  pending_case: bool,
+  /// Whether inside `[[ ]]`, where `<` and `>` compare strings.
+  in_test: bool,
+}
+
+impl BlockState {
+  fn format_line(&mut self, pieces: &[(&ShellToken, bool)]) -> Option<ShellLine<'static>> {
+    if pieces.is_empty() {
+      return None;
+    }
+    let mut tokens = Vec::with_capacity(pieces.len());
+    let mut line_depth = None;
+    let mut in_redirect_target = false;
+    for (i, (token, had_space)) in pieces.iter().enumerate() {
+      let next = pieces.get(i + 1);
+      let is_word_start = i == 0 || *had_space || matches!(pieces[i - 1].0.kind, ShellTokenKind::Operator | ShellTokenKind::Redirection);
+      let is_word_end = next.is_none_or(|(next, had_space)| *had_space || matches!(next.kind, ShellTokenKind::Operator | ShellTokenKind::Redirection));
+      let is_word = token.kind == ShellTokenKind::Word && is_word_start && is_word_end;
+      if is_word_start {
+        in_redirect_target = i > 0 && pieces[i - 1].0.kind == ShellTokenKind::Redirection;
+      }
+      let keyword = match token.text.as_str() {
+        "in" if is_word && self.pending_case => Some("in"),
+        keyword if is_word && !self.not_command_position && !in_redirect_target => Some(keyword),
+        _ => None,
+      };
+
+      if matches!(keyword, Some("fi" | "done" | "esac" | "}" | "else" | "elif")) {
+        self.depth = self.depth.checked_sub(1)?;
+      }
+      line_depth.get_or_insert(self.depth);
+
+      let space = i > 0 && needs_space(pieces[i - 1].0, token, *had_space, self.in_test);
+      let text = match token.kind {
+        ShellTokenKind::Expansion | ShellTokenKind::DoubleQuoted => replace_backticks(&token.text),
+        _ => None,
+      };
+      tokens.push((space, text.unwrap_or_else(|| token.text.clone())));
+
+      match keyword {
+        Some("then" | "do" | "else" | "{") => self.depth += 1,
+        Some("in") => {
+          self.depth += 1;
+          self.pending_case = false;
+        }
+        Some("case") => self.pending_case = true,
+        Some("[[") => self.in_test = true,
+        Some("]]") => self.in_test = false,
+        _ => {}
+      }
+      self.not_command_position = match token.kind {
+        ShellTokenKind::Operator => false,
+        ShellTokenKind::Redirection | ShellTokenKind::Comment => self.not_command_position,
+        _ if in_redirect_target => self.not_command_position,
+        _ => !matches!(keyword, Some("if" | "elif" | "then" | "else" | "while" | "until" | "do" | "in" | "!" | "{")),
+      };
+    }
+    Some(ShellLine::Code {
+      depth: line_depth.unwrap_or(self.depth),
+      tokens,
+    })
+  }
+}
+
+/// Whether to separate two tokens on a line with a space. Operators are
+/// spaced, except that nothing goes before `;`, and a redirection sticks to
+/// its target (ex. `>/dev/null 2>&1`). Otherwise whether the tokens were
+/// separated is kept, since adjacent tokens form one word.
+fn needs_space(previous: &ShellToken, token: &ShellToken, had_space: bool, in_test: bool) -> bool {
+  let is_operator = |token: &ShellToken, operators: &[&str]| token.kind == ShellTokenKind::Operator && operators.contains(&token.text.as_str());
+  if is_operator(previous, &["(", ")"]) || is_operator(token, &["(", ")"]) {
+    // `( (` and `((` differ, as do `a)` and `a )` in a case pattern
+    had_space
+  } else if is_operator(token, &[";", ";;"]) {
+    false
+  } else if previous.kind == ShellTokenKind::Operator || token.kind == ShellTokenKind::Operator {
+    true
+  } else if in_test && (previous.kind == ShellTokenKind::Redirection || token.kind == ShellTokenKind::Redirection) {
+    had_space
+  } else if previous.kind == ShellTokenKind::Redirection {
+    // `< <(a)` and `> >(a)` would become the `<<` and `>>` operators
+    had_space && token.text.starts_with(['<', '>', '('])
+  } else if token.kind == ShellTokenKind::Redirection {
+    true
+  } else {
+    had_space
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::ast::Dockerfile;
+  use crate::ast::Instruction;
+  use crate::ast::ShellOrExecExpr;
+
+  fn format(text: &str) -> Option<Vec<(usize, String)>> {
+    let file = Dockerfile::parse(text).unwrap();
+    let instruction = match &file.instructions[0] {
+      Instruction::Heredoc(heredoc) => &*heredoc.instruction,
+      instruction => instruction,
+    };
+    let Instruction::Run(run) = instruction else { unreachable!() };
+    let ShellOrExecExpr::Shell(value) = &run.expr else { unreachable!() };
+    let lines = format_shell_lines(value)?;
+    Some(
+      lines
+        .into_iter()
+        .map(|line| match line {
+          ShellLine::Code { depth, tokens } => {
+            let text = tokens.into_iter().map(|(space, text)| if space { format!(" {text}") } else { text }).collect();
+            (depth, text)
+          }
+          ShellLine::Comment { depth, .. } => (depth, "#".to_string()),
+        })
+        .collect(),
+    )
+  }
+
+  fn line(depth: usize, text: &str) -> (usize, String) {
+    (depth, text.to_string())
+  }
+
+  #[test]
+  fn spaces_operators_and_redirections() {
+    assert_eq!(
+      format("RUN a&&b||c ;d|e > /dev/null 2>&1 &"),
+      Some(vec![line(0, "a && b || c; d | e >/dev/null 2>&1 &")])
+    );
+    assert_eq!(
+      format("RUN (cd a;b) && x=`date`\"`id`\""),
+      Some(vec![line(0, "(cd a; b) && x=$(date)\"$(id)\"")])
+    );
+    assert_eq!(format("RUN [[ a > b ]]"), Some(vec![line(0, "[[ a > b ]]")]));
+    assert_eq!(format("RUN diff < <(sort a) <(sort b)"), Some(vec![line(0, "diff < <(sort a) <(sort b)")]));
+    assert_eq!(
+      format("RUN echo a > >(tee log)  2> >(cat)"),
+      Some(vec![line(0, "echo a > >(tee log) 2> >(cat)")])
+    );
+  }
+
+  #[test]
+  fn indents_blocks() {
+    assert_eq!(
+      format("RUN if a; then \\\n b; \\\n # c\n elif c; then \\\n for x in 1; do d; done; \\\n else \\\n case $x in \\\n a) e;; \\\n esac; \\\n fi"),
+      Some(vec![
+        line(0, "if a; then"),
+        line(1, "b;"),
+        line(1, "#"),
+        line(0, "elif c; then"),
+        line(1, "for x in 1; do d; done;"),
+        line(0, "else"),
+        line(1, "case $x in"),
+        line(2, "a) e;;"),
+        line(1, "esac;"),
+        line(0, "fi"),
+      ])
+    );
+    // keywords are only recognized as command names
+    assert_eq!(format("RUN echo if then \\\n fi"), Some(vec![line(0, "echo if then"), line(0, "fi")]));
+  }
+
+  #[test]
+  fn falls_back_when_unsafe() {
+    assert!(format("RUN echo \"a").is_none());
+    assert!(format("RUN cat <<EOF\na\nEOF").is_none());
+    assert!(format("RUN if a; then \\\n b").is_none());
+    assert!(format("RUN fi").is_none());
+    assert!(format("RUN a |& b").is_none());
+    assert!(format("RUN echo \"a \\\n b\"").is_none());
+  }
+}
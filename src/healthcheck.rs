@@ -0,0 +1,153 @@
+//! Checking and normalizing the values of `HEALTHCHECK` options, which Docker
+//! parses as Go durations (`--interval`, `--timeout`, `--start-period` and
+//! `--start-interval`) or as an integer (`--retries`).
+
+const DURATION_OPTIONS: &[&str] = &["interval", "timeout", "start-period", "start-interval"];
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+/// The units of a Go duration with their length in nanoseconds, largest first.
+const UNITS: &[(&str, u64)] = &[
+  ("h", 3_600_000_000_000),
+  ("m", 60_000_000_000),
+  ("s", 1_000_000_000),
+  ("ms", NANOS_PER_MILLI),
+  ("us", 1_000),
+  ("µs", 1_000),
+  ("μs", 1_000),
+  ("ns", 1),
+];
+
+/// Checks the value of the `HEALTHCHECK` option `name` the way Docker does,
+/// returning the value in its normal form (ex. `30s` for `0.5m`), or why
+/// Docker would reject it. An empty value stands for the option's default.
+pub(crate) fn check_option(name: &str, value: &str) -> Result<String, String> {
+  if value.is_empty() {
+    return Ok(String::new());
+  }
+  if name == "retries" {
+    return match value.parse::<i32>() {
+      Ok(retries) if retries < 0 => Err("--retries can't be negative".to_string()),
+      Ok(retries) => Ok(retries.to_string()),
+      Err(_) => Err(format!("--retries={} is not a whole number", value)),
+    };
+  }
+  if !DURATION_OPTIONS.contains(&name) {
+    return Err(format!("--{} is not a HEALTHCHECK option", name));
+  }
+  match parse_duration(value) {
+    Some(0) => Ok(format_duration(0)),
+    Some(nanos) if nanos < NANOS_PER_MILLI as i64 => Err(format!("--{} has to be 0 or at least 1ms", name)),
+    Some(nanos) => Ok(format_duration(nanos as u64)),
+    None => Err(format!("--{}={} is not a valid duration (ex. 30s or 1m30s)", name, value)),
+  }
+}
+
+/// Parses a duration the way Go's `time.ParseDuration` does, to nanoseconds.
+fn parse_duration(text: &str) -> Option<i64> {
+  let (is_negative, text) = match text.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, text.strip_prefix('+').unwrap_or(text)),
+  };
+  if text == "0" {
+    return Some(0);
+  }
+  if text.is_empty() {
+    return None;
+  }
+  let mut total: u128 = 0;
+  let mut rest = text;
+  while !rest.is_empty() {
+    let int_len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let (int_part, after_int) = rest.split_at(int_len);
+    let (fraction, after_number) = match after_int.strip_prefix('.') {
+      Some(after_dot) => after_dot.split_at(after_dot.find(|c: char| !c.is_ascii_digit()).unwrap_or(after_dot.len())),
+      None => ("", after_int),
+    };
+    if int_part.is_empty() && fraction.is_empty() {
+      return None;
+    }
+    let unit_len = after_number.find(|c: char| c == '.' || c.is_ascii_digit()).unwrap_or(after_number.len());
+    let (unit, after_unit) = after_number.split_at(unit_len);
+    let (_, unit_nanos) = UNITS.iter().find(|(name, _)| *name == unit)?;
+    let unit_nanos = *unit_nanos as u128;
+    let int_value = if int_part.is_empty() { 0 } else { int_part.parse::<u128>().ok()? };
+    total = total.checked_add(int_value.checked_mul(unit_nanos)?)?;
+    // like Go, fractions of a nanosecond are dropped
+    let mut scale = 1_u128;
+    let mut fraction_value = 0_u128;
+    for digit in fraction.bytes().take(18) {
+      fraction_value = fraction_value * 10 + (digit - b'0') as u128;
+      scale *= 10;
+    }
+    total = total.checked_add(fraction_value * unit_nanos / scale)?;
+    if total > i64::MAX as u128 + 1 {
+      return None;
+    }
+    rest = after_unit;
+  }
+  match is_negative {
+    true => Some((total as i128).wrapping_neg() as i64),
+    false => i64::try_from(total).ok(),
+  }
+}
+
+/// Writes a duration with the largest units first and without the parts that
+/// are zero (ex. `1h30m` or `1s500ms`).
+fn format_duration(nanos: u64) -> String {
+  if nanos == 0 {
+    return "0s".to_string();
+  }
+  let mut text = String::new();
+  let mut rest = nanos;
+  for (unit, unit_nanos) in UNITS.iter().filter(|(unit, _)| unit.is_ascii()) {
+    let count = rest / unit_nanos;
+    if count > 0 {
+      text.push_str(&format!("{}{}", count, unit));
+      rest %= unit_nanos;
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn parses_go_durations() {
+    assert_eq!(parse_duration("30s"), Some(30_000_000_000));
+    assert_eq!(parse_duration("1h30m"), Some(5_400_000_000_000));
+    assert_eq!(parse_duration("0.5m"), Some(30_000_000_000));
+    assert_eq!(parse_duration(".5s"), Some(500_000_000));
+    assert_eq!(parse_duration("1.5µs"), Some(1_500));
+    assert_eq!(parse_duration("-2ms"), Some(-2_000_000));
+    assert_eq!(parse_duration("0"), Some(0));
+    for text in ["", "30", "s", ".s", "1x", "1.2.3s", "9999999999h", "1 s"] {
+      assert_eq!(parse_duration(text), None, "{}", text);
+    }
+  }
+
+  #[test]
+  fn normalizes_option_values() {
+    assert_eq!(check_option("interval", "0.5m").unwrap(), "30s");
+    assert_eq!(check_option("timeout", "90m").unwrap(), "1h30m");
+    assert_eq!(check_option("start-period", "1500ms").unwrap(), "1s500ms");
+    assert_eq!(check_option("start-interval", "0").unwrap(), "0s");
+    assert_eq!(check_option("retries", "03").unwrap(), "3");
+    assert_eq!(check_option("interval", "").unwrap(), "");
+  }
+
+  #[test]
+  fn rejects_what_docker_rejects() {
+    assert_eq!(
+      check_option("interval", "30").unwrap_err(),
+      "--interval=30 is not a valid duration (ex. 30s or 1m30s)"
+    );
+    assert_eq!(check_option("timeout", "500us").unwrap_err(), "--timeout has to be 0 or at least 1ms");
+    assert_eq!(check_option("interval", "-1s").unwrap_err(), "--interval has to be 0 or at least 1ms");
+    assert_eq!(check_option("retries", "-1").unwrap_err(), "--retries can't be negative");
+    assert_eq!(check_option("retries", "three").unwrap_err(), "--retries=three is not a whole number");
+    assert_eq!(check_option("start-perod", "5s").unwrap_err(), "--start-perod is not a HEALTHCHECK option");
+  }
+}
@@ -0,0 +1,96 @@
+//! The functions of the npm package's JavaScript API, which is built with
+//! wasm-bindgen from the `js` feature. Values cross the boundary as JSON text
+//! that `deployment/npm/index.js` converts to and from objects.
+
+use std::path::Path;
+
+use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::GlobalConfiguration;
+use wasm_bindgen::prelude::*;
+
+use crate::ast::Dockerfile;
+use crate::configuration::Configuration;
+use crate::configuration::resolve_config;
+
+/// Formats `text` with the configuration in `config_json` (an object in the
+/// shape of the plugin's schema.json), returning the text unchanged when it's
+/// already formatted.
+#[wasm_bindgen(js_name = format)]
+pub fn js_format(text: &str, config_json: &str) -> Result<String, JsError> {
+  format_json(text, config_json).map_err(|message| JsError::new(&message))
+}
+
+/// Parses `text`, returning its AST as JSON in the shape of ast.schema.json.
+#[wasm_bindgen(js_name = parse)]
+pub fn js_parse(text: &str) -> Result<String, JsError> {
+  parse_json(text).map_err(|message| JsError::new(&message))
+}
+
+/// Gets the diagnostics of `text` formatted with the configuration in
+/// `config_json`, as a JSON array.
+#[wasm_bindgen(js_name = diagnostics)]
+pub fn js_diagnostics(text: &str, config_json: &str) -> Result<String, JsError> {
+  diagnostics_json(text, config_json).map_err(|message| JsError::new(&message))
+}
+
+fn format_json(text: &str, config_json: &str) -> Result<String, String> {
+  let config = config_from_json(config_json)?;
+  let formatted = crate::format_text(Path::new("Dockerfile"), text, &config).map_err(|err| err.to_string())?;
+  Ok(formatted.unwrap_or_else(|| text.to_string()))
+}
+
+fn parse_json(text: &str) -> Result<String, String> {
+  let file = Dockerfile::parse(text).map_err(|err| err.to_string())?;
+  serde_json::to_string(&file).map_err(|err| err.to_string())
+}
+
+fn diagnostics_json(text: &str, config_json: &str) -> Result<String, String> {
+  let config = config_from_json(config_json)?;
+  let file = Dockerfile::parse(text).map_err(|err| err.to_string())?;
+  serde_json::to_string(&crate::diagnostics(&file, &config)).map_err(|err| err.to_string())
+}
+
+/// Resolves the configuration the same way the CLI's `--config` file is, where
+/// any configuration diagnostic is an error.
+fn config_from_json(config_json: &str) -> Result<Configuration, String> {
+  let mut config_map = serde_json::from_str::<ConfigKeyMap>(config_json).map_err(|err| format!("invalid configuration: {}", err))?;
+  config_map.shift_remove("$schema");
+  let result = resolve_config(config_map, &GlobalConfiguration::default());
+  if result.diagnostics.is_empty() {
+    Ok(result.config)
+  } else {
+    let messages = result.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>();
+    Err(format!("invalid configuration\n  {}", messages.join("\n  ")))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn formats_with_json_config() {
+    assert_eq!(format_json("from    alpine\n", "{}").unwrap(), "FROM alpine\n");
+    assert_eq!(format_json("FROM alpine\n", r#"{ "newLineKind": "crlf" }"#).unwrap(), "FROM alpine\r\n");
+    assert_eq!(format_json("FROM alpine\n", "{}").unwrap(), "FROM alpine\n");
+    let err = format_json("FROM alpine\n", r#"{ "unknownOption": true }"#).unwrap_err();
+    assert!(err.starts_with("invalid configuration\n") && err.contains("unknownOption"), "{}", err);
+    assert!(format_json("FROM alpine\n", "[]").unwrap_err().starts_with("invalid configuration: "));
+  }
+
+  #[test]
+  fn parses_to_json() {
+    let value = serde_json::from_str::<serde_json::Value>(&parse_json("FROM alpine\n").unwrap()).unwrap();
+    assert_eq!(value["instructions"][0]["kind"], "from");
+    assert_eq!(value["instructions"][0]["image"]["content"], "alpine");
+  }
+
+  #[test]
+  fn gets_diagnostics_as_json() {
+    assert_eq!(
+      diagnostics_json("ONBUILD FROM alpine\n", "{}").unwrap(),
+      r#"[{"span":{"start":0,"end":19},"message":"FROM isn't allowed as an ONBUILD trigger"}]"#
+    );
+    assert_eq!(diagnostics_json("FROM alpine\n", "{}").unwrap(), "[]");
+  }
+}
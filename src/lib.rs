@@ -1,17 +1,43 @@
 pub mod ast;
+#[cfg(feature = "diff")]
+mod check_text;
 pub mod configuration;
+mod diagnostics;
 mod error;
+mod exec_form;
+mod flag_order;
+mod format_range;
 mod format_text;
 mod generation;
+mod healthcheck;
+#[cfg(feature = "js")]
+mod js_api;
 mod parser;
+mod quoting;
+mod semantics;
+pub mod shell;
 
+#[cfg(feature = "diff")]
+pub use check_text::DiffHunk;
+#[cfg(feature = "diff")]
+pub use check_text::check_text;
+pub use diagnostics::Diagnostic;
+pub use diagnostics::diagnostics;
 pub use error::FormatError;
 pub use error::ParseError;
+pub use format_range::TextEdit;
+pub use format_range::format_range;
+pub use format_text::format_dockerfile;
+pub use format_text::format_file;
+pub use format_text::format_str;
 pub use format_text::format_text;
 
 #[cfg(feature = "tracing")]
 pub use format_text::trace_file;
 
+#[cfg(all(feature = "wasm", feature = "js", target_arch = "wasm32"))]
+compile_error!("the wasm and js features build different Wasm modules, so they can't be enabled together");
+
 #[cfg(feature = "wasm")]
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 mod wasm_plugin;
@@ -0,0 +1,304 @@
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::GlobalConfiguration;
+use dprint_plugin_dockerfile::check_text;
+use dprint_plugin_dockerfile::configuration::Configuration;
+use dprint_plugin_dockerfile::configuration::resolve_config;
+use dprint_plugin_dockerfile::format_text;
+
+const USAGE: &str = "Usage: dprint-dockerfile [OPTIONS] [FILES]...
+
+Formats Dockerfiles. Reads from stdin when no files are given or a file is `-`.
+
+Options:
+  --check          Exit with status 1 if any file isn't formatted
+  --write          Write the formatted text back to the files
+  --diff           Print a diff of the changes instead of the formatted text
+  --config <FILE>  A JSON configuration file in the shape of the plugin's schema.json
+  -h, --help       Print this help
+  -V, --version    Print the version";
+
+/// Exit status when `--check` finds unformatted files.
+const EXIT_UNFORMATTED: i32 = 1;
+/// Exit status for invalid arguments or configuration, or a file that couldn't
+/// be read, parsed or written.
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+  check: bool,
+  write: bool,
+  diff: bool,
+  config: Option<PathBuf>,
+  files: Vec<PathBuf>,
+  help: bool,
+  version: bool,
+}
+
+fn main() {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut stdin = std::io::stdin().lock();
+  let mut stdout = std::io::stdout().lock();
+  let mut stderr = std::io::stderr().lock();
+  std::process::exit(run(&args, &mut stdin, &mut stdout, &mut stderr));
+}
+
+fn run(args: &[String], stdin: &mut impl Read, stdout: &mut impl Write, stderr: &mut impl Write) -> i32 {
+  let args = match parse_args(args) {
+    Ok(args) => args,
+    Err(message) => {
+      let _ = writeln!(stderr, "error: {}\n\n{}", message, USAGE);
+      return EXIT_ERROR;
+    }
+  };
+  if args.help {
+    let _ = writeln!(stdout, "{}", USAGE);
+    return 0;
+  }
+  if args.version {
+    let _ = writeln!(stdout, "{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    return 0;
+  }
+  let config = match load_config(args.config.as_deref()) {
+    Ok(config) => config,
+    Err(message) => {
+      let _ = writeln!(stderr, "error: {}", message);
+      return EXIT_ERROR;
+    }
+  };
+
+  let stdin_path = PathBuf::from("-");
+  let files = if args.files.is_empty() {
+    std::slice::from_ref(&stdin_path)
+  } else {
+    &args.files[..]
+  };
+  let mut exit_code = 0;
+  for path in files {
+    match format_file(path, &args, &config, stdin, stdout, stderr) {
+      Ok(true) => {}
+      Ok(false) => {
+        if args.check {
+          exit_code = exit_code.max(EXIT_UNFORMATTED);
+        }
+      }
+      Err(message) => {
+        let _ = writeln!(stderr, "error: {}: {}", display_path(path), message);
+        exit_code = EXIT_ERROR;
+      }
+    }
+  }
+  exit_code
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+  let mut result = Args::default();
+  let mut args = args.iter();
+  let mut only_files = false;
+  while let Some(arg) = args.next() {
+    if only_files || arg == "-" || !arg.starts_with('-') {
+      result.files.push(PathBuf::from(arg));
+      continue;
+    }
+    match arg.as_str() {
+      "--check" => result.check = true,
+      "--write" => result.write = true,
+      "--diff" => result.diff = true,
+      "--config" => match args.next() {
+        Some(path) => result.config = Some(PathBuf::from(path)),
+        None => return Err("--config requires a file path".to_string()),
+      },
+      "-h" | "--help" => result.help = true,
+      "-V" | "--version" => result.version = true,
+      "--" => only_files = true,
+      _ => match arg.strip_prefix("--config=") {
+        Some(path) => result.config = Some(PathBuf::from(path)),
+        None => return Err(format!("unknown option '{}'", arg)),
+      },
+    }
+  }
+  if result.write && result.files.iter().any(|path| is_stdin(path)) {
+    return Err("--write can't be used with stdin".to_string());
+  }
+  Ok(result)
+}
+
+/// Resolves the configuration from a JSON file, or the defaults when there is
+/// none. Any configuration diagnostic is an error.
+fn load_config(path: Option<&Path>) -> Result<Configuration, String> {
+  let mut config_map = match path {
+    Some(path) => {
+      let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+      serde_json::from_str::<ConfigKeyMap>(&text).map_err(|err| format!("{}: {}", path.display(), err))?
+    }
+    None => ConfigKeyMap::new(),
+  };
+  config_map.shift_remove("$schema");
+  let result = resolve_config(config_map, &GlobalConfiguration::default());
+  if result.diagnostics.is_empty() {
+    Ok(result.config)
+  } else {
+    let messages = result.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>();
+    Err(format!("invalid configuration\n  {}", messages.join("\n  ")))
+  }
+}
+
+/// Formats a file (or stdin) according to the mode in `args`. Returns whether
+/// it was already formatted.
+fn format_file(
+  path: &Path,
+  args: &Args,
+  config: &Configuration,
+  stdin: &mut impl Read,
+  stdout: &mut impl Write,
+  stderr: &mut impl Write,
+) -> Result<bool, String> {
+  let text = if is_stdin(path) {
+    let mut text = String::new();
+    stdin.read_to_string(&mut text).map_err(|err| err.to_string())?;
+    text
+  } else {
+    std::fs::read_to_string(path).map_err(|err| err.to_string())?
+  };
+  if args.diff {
+    let hunks = check_text(path, &text, config).map_err(|err| err.to_string())?;
+    if !hunks.is_empty() {
+      let name = display_path(path);
+      write!(stdout, "--- {}\n+++ {}\n", name, name).map_err(|err| err.to_string())?;
+      for hunk in &hunks {
+        write!(stdout, "{}", hunk).map_err(|err| err.to_string())?;
+      }
+    }
+    if !args.write {
+      return Ok(hunks.is_empty());
+    }
+  }
+  let formatted = format_text(path, &text, config).map_err(|err| err.to_string())?;
+  let is_formatted = formatted.is_none();
+  if !args.diff {
+    if args.check {
+      if !is_formatted {
+        writeln!(stderr, "{}: not formatted", display_path(path)).map_err(|err| err.to_string())?;
+      }
+    } else if !args.write {
+      write!(stdout, "{}", formatted.as_deref().unwrap_or(&text)).map_err(|err| err.to_string())?;
+    }
+  }
+  if args.write
+    && let Some(formatted) = &formatted
+  {
+    std::fs::write(path, formatted).map_err(|err| err.to_string())?;
+  }
+  Ok(is_formatted)
+}
+
+fn is_stdin(path: &Path) -> bool {
+  path == Path::new("-")
+}
+
+fn display_path(path: &Path) -> String {
+  if is_stdin(path) { "<stdin>".to_string() } else { path.display().to_string() }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn run_with(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let code = run(&args, &mut stdin.as_bytes(), &mut stdout, &mut stderr);
+    (code, String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+  }
+
+  /// Gets a temp directory for `test`, separate from the other tests that run
+  /// at the same time so each can remove its own.
+  fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dprint-dockerfile-cli-{}-{}", std::process::id(), test));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn temp_file(dir: &Path, name: &str, text: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path
+  }
+
+  #[test]
+  fn parses_args() {
+    let args = ["--check", "--config=a.json", "--", "--write", "Dockerfile"].map(String::from);
+    assert_eq!(
+      parse_args(&args).unwrap(),
+      Args {
+        check: true,
+        config: Some(PathBuf::from("a.json")),
+        files: vec![PathBuf::from("--write"), PathBuf::from("Dockerfile")],
+        ..Default::default()
+      }
+    );
+    assert_eq!(parse_args(&["--nope".to_string()]).unwrap_err(), "unknown option '--nope'");
+    assert_eq!(parse_args(&["--config".to_string()]).unwrap_err(), "--config requires a file path");
+    assert!(parse_args(&["--write".to_string(), "-".to_string()]).is_err());
+  }
+
+  #[test]
+  fn formats_stdin() {
+    assert_eq!(run_with(&[], "FROM    alpine\n"), (0, "FROM alpine\n".to_string(), String::new()));
+  }
+
+  #[test]
+  fn reports_unreadable_files() {
+    let path = std::env::temp_dir().join("dprint-dockerfile-cli-missing").join("Dockerfile");
+    let (code, _, stderr) = run_with(&["--check", path.to_str().unwrap()], "");
+    assert_eq!(code, EXIT_ERROR);
+    assert!(stderr.starts_with(&format!("error: {}: ", path.display())), "{}", stderr);
+  }
+
+  #[test]
+  fn checks() {
+    assert_eq!(run_with(&["--check"], "FROM alpine\n"), (0, String::new(), String::new()));
+    assert_eq!(
+      run_with(&["--check"], "FROM    alpine\n"),
+      (EXIT_UNFORMATTED, String::new(), "<stdin>: not formatted\n".to_string())
+    );
+  }
+
+  #[test]
+  fn prints_diff() {
+    let (code, stdout, _) = run_with(&["--diff", "--check"], "FROM alpine\nRUN    a\n");
+    assert_eq!(code, EXIT_UNFORMATTED);
+    assert_eq!(stdout, "--- <stdin>\n+++ <stdin>\n@@ -2,1 +2,1 @@\n-RUN    a\n+RUN a\n");
+    assert_eq!(run_with(&["--diff"], "FROM alpine\n"), (0, String::new(), String::new()));
+  }
+
+  #[test]
+  fn writes_files() {
+    let dir = temp_dir("write");
+    let path = temp_file(&dir, "Dockerfile.write", "FROM    alpine\n");
+    let (code, stdout, _) = run_with(&["--write", path.to_str().unwrap()], "");
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((code, stdout.as_str()), (0, ""));
+    assert_eq!(written, "FROM alpine\n");
+  }
+
+  #[test]
+  fn uses_config_file() {
+    let dir = temp_dir("config");
+    let config = temp_file(&dir, "config.json", r#"{ "$schema": "schema.json", "newLineKind": "crlf" }"#);
+    let (valid_code, valid_stdout, _) = run_with(&["--config", config.to_str().unwrap()], "FROM alpine\n");
+    let config = temp_file(&dir, "invalid.json", r#"{ "unknownOption": true }"#);
+    let (code, _, stderr) = run_with(&["--config", config.to_str().unwrap()], "FROM alpine\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!((valid_code, valid_stdout.as_str()), (0, "FROM alpine\r\n"));
+    assert_eq!(code, EXIT_ERROR);
+    assert!(stderr.contains("unknownOption"), "{}", stderr);
+  }
+}
@@ -7,51 +7,158 @@
 
 use monch::*;
 
+use crate::TextEdit;
 use crate::ast::*;
 
 type PResult<'a, T> = Result<(&'a str, T), ParseErrorFailureError>;
 
 /// Parses a Dockerfile from a string.
 pub fn parse(text: &str) -> Result<Dockerfile, ParseErrorFailureError> {
-  let escape = detect_escape(text);
+  let directives = parse_directives(text);
+  let escape = directive_escape(&directives);
   let parser = Parser { base: text, escape };
   let instructions = parser.parse_dockerfile(text)?;
   Ok(Dockerfile {
     content: text.to_string(),
+    directives,
     instructions,
     escape,
+    line_starts: line_starts(text),
   })
 }
 
-/// Determines the escape character from a leading `# escape=` parser directive,
-/// defaulting to `\`. Parser directives are `# name=value` comment lines at the
-/// very top of the file; scanning stops at the first line that isn't one.
-fn detect_escape(text: &str) -> char {
-  for line in text.lines() {
-    let trimmed = line.trim();
-    let Some(directive) = trimmed.strip_prefix('#') else {
-      break; // a blank line or instruction ends the directive section
+/// Parses the result of applying `edit` to `previous`'s content. Instructions
+/// before the edit are kept, and once the parser is back in step with the
+/// previous parse (an instruction after the edit starts where one did before),
+/// the remaining instructions are reused with their spans shifted.
+pub fn reparse(previous: &Dockerfile, edit: &TextEdit) -> Result<Dockerfile, ParseErrorFailureError> {
+  let text = edit.apply(&previous.content);
+  let directives = parse_directives(&text);
+  let escape = directive_escape(&directives);
+  let old = &previous.instructions;
+  if escape != previous.escape || old.is_empty() {
+    // a changed escape directive affects how the whole file is parsed
+    return parse(&text);
+  }
+
+  let delta = edit.new_text.len() as isize - edit.range.len() as isize;
+  let edit_end = edit.range.start + edit.new_text.len();
+  // also reparse the instruction before the first one the edit touches, since
+  // the edit may extend it (e.g. by adding a line continuation after it)
+  let first = old.partition_point(|i| i.span().end < edit.range.start).saturating_sub(1);
+  let mut instructions = old[..first].to_vec();
+
+  let parser = Parser { base: &text, escape };
+  let reparse_start = if first == 0 { 0 } else { old[first].span().start };
+  let mut input = &text[reparse_start..];
+  while let Some(start) = parser.skip_to_instruction(input) {
+    let start_offset = parser.off(start);
+    if start_offset >= edit_end {
+      let old_start = start_offset.wrapping_add_signed(-delta);
+      if let Ok(index) = old.binary_search_by_key(&old_start, |i| i.span().start) {
+        instructions.extend(old[index..].iter().cloned().map(|mut instruction| {
+          instruction.shift_spans(delta);
+          instruction
+        }));
+        break;
+      }
+    }
+    let Some((rest, instruction)) = parser.parse_next(start) else { break };
+    instructions.push(instruction);
+    input = rest;
+  }
+
+  Ok(Dockerfile {
+    line_starts: reparse_line_starts(previous, edit),
+    content: text,
+    directives,
+    instructions,
+    escape,
+  })
+}
+
+/// Updates the line-start table of `previous` for `edit` without rescanning the
+/// unchanged text.
+fn reparse_line_starts(previous: &Dockerfile, edit: &TextEdit) -> Vec<usize> {
+  let old = &previous.line_starts;
+  let delta = edit.new_text.len() as isize - edit.range.len() as isize;
+  // a line start `s` follows the newline at `s - 1`, which the edit removes when
+  // it lies within the replaced range
+  let before = old.partition_point(|s| *s <= edit.range.start);
+  let after = old.partition_point(|s| *s <= edit.range.end);
+  old[..before]
+    .iter()
+    .copied()
+    .chain(edit.new_text.match_indices('\n').map(|(i, _)| edit.range.start + i + 1))
+    .chain(old[after..].iter().map(|s| s.wrapping_add_signed(delta)))
+    .collect()
+}
+
+/// The parser directives BuildKit recognizes.
+const DIRECTIVE_NAMES: [&str; 3] = ["syntax", "escape", "check"];
+
+/// Parses the `# name=value` parser directives at the very top of the file,
+/// following BuildKit's rules: names are case-insensitive, whitespace around
+/// the `=` is allowed, and the directive section ends at the first line that
+/// isn't a known, not yet seen directive (a blank line, an instruction, a
+/// regular comment, an unknown or a repeated directive), which is then treated
+/// as a comment.
+fn parse_directives(text: &str) -> Vec<ParserDirective> {
+  let mut directives: Vec<ParserDirective> = Vec::new();
+  let mut line_start = 0;
+  for line in text.split_inclusive('\n') {
+    let offset = line_start;
+    line_start += line.len();
+    let trimmed_start = line.trim_start();
+    let Some(directive) = trimmed_start.strip_prefix('#') else {
+      break;
     };
     let Some((name, value)) = directive.split_once('=') else {
-      break; // a regular comment ends the directive section
+      break;
     };
-    match name.trim().to_ascii_lowercase().as_str() {
-      "escape" => return if value.trim() == "`" { '`' } else { '\\' },
-      "syntax" => continue, // another directive — keep looking
-      _ => break,           // unknown directive is treated as a comment
+    let name_text = name.trim();
+    let value_text = value.trim();
+    let lower_name = name_text.to_ascii_lowercase();
+    if !DIRECTIVE_NAMES.contains(&lower_name.as_str()) || value_text.is_empty() || directives.iter().any(|d| d.name.content == lower_name) {
+      break;
     }
+
+    // `#`, the name with its surrounding whitespace, `=`, then the value
+    let start = offset + (line.len() - trimmed_start.len());
+    let name_start = start + 1 + (name.len() - name.trim_start().len());
+    let value_start = start + 1 + name.len() + 1 + (value.len() - value.trim_start().len());
+    directives.push(ParserDirective {
+      span: Span::new(start, value_start + value_text.len()),
+      name: SpannedString {
+        span: Span::new(name_start, name_start + name_text.len()),
+        content: lower_name,
+      },
+      value: SpannedString {
+        span: Span::new(value_start, value_start + value_text.len()),
+        content: value_text.to_string(),
+      },
+    });
+  }
+  directives
+}
+
+/// The escape character set by an `# escape=` directive, defaulting to `\`.
+fn directive_escape(directives: &[ParserDirective]) -> char {
+  match directives.iter().find(|d| d.name.content == "escape") {
+    Some(directive) if directive.value.content == "`" => '`',
+    _ => '\\',
   }
-  '\\'
 }
 
 /// The set of instruction keywords that have dedicated parsing. Everything else
 /// is parsed as a [`MiscInstruction`].
-const KEYWORDS: [&str; 11] = [
+const KEYWORDS: [&str; 12] = [
   "from",
   "run",
   "arg",
   "label",
   "copy",
+  "add",
   "entrypoint",
   "cmd",
   "env",
@@ -70,11 +177,45 @@ struct Parser<'a> {
 impl<'a> Parser<'a> {
   fn parse_dockerfile(&self, mut input: &'a str) -> Result<Vec<Instruction>, ParseErrorFailureError> {
     let mut instructions = Vec::new();
+    while let Some((rest, instruction)) = self.parse_next(input) {
+      instructions.push(instruction);
+      input = rest;
+    }
+    Ok(instructions)
+  }
+
+  /// Skips blank and comment lines, then parses the next instruction. Returns
+  /// `None` once the input is exhausted.
+  fn parse_next(&self, input: &'a str) -> Option<(&'a str, Instruction)> {
+    let after_ws = self.skip_to_instruction(input)?;
+
+    // try to parse a structured instruction; if the line can't be parsed
+    // (a bare keyword, too few arguments, trailing junk, ...), keep it
+    // verbatim so one malformed line doesn't fail the whole file
+    let parsed = (|| {
+      let (rest, instruction) = self.parse_instruction(after_ws)?;
+      let (rest, instruction) = self.maybe_consume_heredocs(instruction, rest);
+      let next = self.finish_line(rest)?;
+      Ok::<_, ParseErrorFailureError>((next, instruction))
+    })();
+    match parsed {
+      Ok((next, instruction)) => Some((next, instruction)),
+      Err(_) => {
+        let (rest, line) = self.unknown_line(after_ws);
+        Some((rest, Instruction::Unknown(line)))
+      }
+    }
+  }
+
+  /// Skips empty lines, standalone comment lines and insignificant whitespace,
+  /// returning the input positioned at the start of the next instruction (or
+  /// `None` at the end of input).
+  fn skip_to_instruction(&self, mut input: &'a str) -> Option<&'a str> {
     loop {
       // a meta step starts with optional insignificant whitespace
       let after_ws = skip_ws(input);
       if after_ws.is_empty() {
-        break;
+        return None;
       }
       if let Some(rest) = strip_newline(after_ws) {
         // empty line
@@ -87,29 +228,8 @@ impl<'a> Parser<'a> {
         input = skip_to_next_line(after_ws);
         continue;
       }
-
-      // try to parse a structured instruction; if the line can't be parsed
-      // (a bare keyword, too few arguments, trailing junk, ...), keep it
-      // verbatim so one malformed line doesn't fail the whole file
-      let parsed = (|| {
-        let (rest, instruction) = self.parse_instruction(after_ws)?;
-        let (rest, instruction) = self.maybe_consume_heredocs(instruction, rest);
-        let next = self.finish_line(rest)?;
-        Ok::<_, ParseErrorFailureError>((next, instruction))
-      })();
-      match parsed {
-        Ok((next, instruction)) => {
-          instructions.push(instruction);
-          input = next;
-        }
-        Err(_) => {
-          let (rest, line) = self.unknown_line(after_ws);
-          instructions.push(Instruction::Unknown(line));
-          input = rest;
-        }
-      }
+      return Some(after_ws);
     }
-    Ok(instructions)
   }
 
   fn parse_instruction(&self, input: &'a str) -> PResult<'a, Instruction> {
@@ -130,12 +250,15 @@ impl<'a> Parser<'a> {
           "entrypoint" => self.parse_shell_or_exec(shell_start, start, ExprKind::Entrypoint),
           "arg" => self.parse_arg(after_arg_ws, start),
           "label" => self.parse_label(after_kw, start),
-          "copy" => self.parse_copy(after_kw, start),
+          "copy" => self.parse_copy(after_kw, start, CopyKind::Copy),
+          // like HEALTHCHECK, an ADD that doesn't parse (ex. a lone source)
+          // stays a misc instruction
+          "add" => self.parse_copy(after_kw, start, CopyKind::Add).or_else(|_| self.parse_misc(input)),
           "env" => self.parse_env(after_kw, start),
           "shell" => self.parse_shell_or_exec(shell_start, start, ExprKind::Shell),
-          // these wrap or normalize nested content; fall back to a misc
-          // instruction if the structured form doesn't parse
-          "onbuild" => self.parse_onbuild(after_arg_ws, start).or_else(|_| self.parse_misc(input)),
+          "onbuild" => self.parse_onbuild(after_arg_ws, start),
+          // this normalizes nested content; fall back to a misc instruction if
+          // the structured form doesn't parse
           "healthcheck" => self.parse_healthcheck(after_arg_ws, start).or_else(|_| self.parse_misc(input)),
           _ => unreachable!(),
         };
@@ -238,7 +361,7 @@ impl<'a> Parser<'a> {
     }
   }
 
-  fn parse_copy(&self, input: &'a str, start: usize) -> PResult<'a, Instruction> {
+  fn parse_copy(&self, input: &'a str, start: usize, kind: CopyKind) -> PResult<'a, Instruction> {
     let mut flags = Vec::new();
     let mut paths: Vec<SpannedString> = Vec::new();
     let mut input = input;
@@ -261,14 +384,7 @@ impl<'a> Parser<'a> {
       && let Ok((rest, array)) = self.string_array(after_ws)
     {
       let span = Span::new(start, array.span.end);
-      return Ok((
-        rest,
-        Instruction::Copy(CopyInstruction {
-          span,
-          flags,
-          args: CopyArgs::Exec(array),
-        }),
-      ));
+      return Ok((rest, kind.build(span, flags, CopyArgs::Exec(array))));
     }
 
     // (arg_ws ~ copy_pathspec){2,}
@@ -287,14 +403,7 @@ impl<'a> Parser<'a> {
     }
     let destination = paths.pop().unwrap();
     let span = Span::new(start, destination.span.end);
-    Ok((
-      input,
-      Instruction::Copy(CopyInstruction {
-        span,
-        flags,
-        args: CopyArgs::Paths { sources: paths, destination },
-      }),
-    ))
+    Ok((input, kind.build(span, flags, CopyArgs::Paths { sources: paths, destination })))
   }
 
   fn parse_label(&self, after_kw: &'a str, start: usize) -> PResult<'a, Instruction> {
@@ -470,7 +579,12 @@ impl<'a> Parser<'a> {
   }
 
   fn parse_onbuild(&self, input: &'a str, start: usize) -> PResult<'a, Instruction> {
-    let (rest, inner) = self.parse_instruction(input)?;
+    // a trigger whose structured form doesn't parse (ex. `FROM` without an
+    // image) is kept as a misc instruction, so it's still known to be a trigger
+    let (rest, inner) = self
+      .parse_instruction(input)
+      .or_else(|_| self.parse_misc(input))
+      .or_else(|_| self.parse_bare_keyword(input))?;
     let span = Span::new(start, inner.span().end);
     Ok((
       rest,
@@ -529,6 +643,24 @@ impl<'a> Parser<'a> {
     Ok((rest, Instruction::Misc(MiscInstruction { span, instruction, arguments })))
   }
 
+  /// Parses a keyword with no arguments as a misc instruction with empty
+  /// arguments, for an `ONBUILD` trigger such as `ONBUILD FROM`.
+  fn parse_bare_keyword(&self, input: &'a str) -> PResult<'a, Instruction> {
+    let start = self.off(input);
+    let (after_kw, keyword) = alpha0(input);
+    if keyword.is_empty() {
+      return Err(fail("unexpected character"));
+    }
+    let instruction = self.spanned(input, after_kw, keyword.to_string());
+    let end = self.off(after_kw);
+    let arguments = BreakableString {
+      span: Span::new(end, end),
+      components: Vec::new(),
+    };
+    let span = Span::new(start, end);
+    Ok((after_kw, Instruction::Misc(MiscInstruction { span, instruction, arguments })))
+  }
+
   // -- shared token parsers --
 
   /// Parses a `--name=value` flag whose name is ASCII-alphabetic (as in `FROM`
@@ -902,6 +1034,22 @@ impl ExprKind {
   }
 }
 
+/// Which instruction taking `COPY`'s arguments is being parsed.
+#[derive(Clone, Copy)]
+enum CopyKind {
+  Copy,
+  Add,
+}
+
+impl CopyKind {
+  fn build(self, span: Span, flags: Vec<CopyFlag>, args: CopyArgs) -> Instruction {
+    match self {
+      CopyKind::Copy => Instruction::Copy(CopyInstruction { span, flags, args }),
+      CopyKind::Add => Instruction::Add(AddInstruction { span, flags, args }),
+    }
+  }
+}
+
 /// A heredoc declaration found on an instruction's first line.
 struct Heredoc {
   /// The delimiter word (without surrounding quotes).
@@ -1235,6 +1383,67 @@ mod test {
     }
   }
 
+  #[test]
+  fn parses_directives() {
+    let text = "# Syntax = docker/dockerfile:1\n#check=skip=JSONArgsRecommended\n# escape=`\n# foo=bar\n# escape=\\\nFROM a\n";
+    let file = Dockerfile::parse(text).unwrap();
+    let directives = file
+      .directives
+      .iter()
+      .map(|d| (d.name.content.as_str(), d.value.content.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      directives,
+      [("syntax", "docker/dockerfile:1"), ("check", "skip=JSONArgsRecommended"), ("escape", "`")]
+    );
+    assert_eq!(file.escape, '`');
+    let syntax = &file.directives[0];
+    assert_eq!(&text[syntax.span.start..syntax.span.end], "# Syntax = docker/dockerfile:1");
+    assert_eq!(&text[syntax.name.span.start..syntax.name.span.end], "Syntax");
+    assert_eq!(&text[syntax.value.span.start..syntax.value.span.end], "docker/dockerfile:1");
+  }
+
+  #[test]
+  fn reparse_matches_full_parse() {
+    let text = "# escape=\\\nFROM a AS b\n\nRUN echo \\\n  hi\n# c\nCOPY x y\nRUN <<EOF\nbody\nEOF\nENV A=1\nCMD [\"a\"]\n";
+    let edits = [
+      (0, 0, "FROM z\n"),
+      (12, 13, "c"),
+      (19, 19, "ARG X\n"),
+      (28, 33, ""),
+      (31, 35, "\nRUN y \\\n"),
+      (40, 41, ""),
+      (49, 57, ""),
+      (text.len(), text.len(), "LABEL a=b"),
+      (text.len() - 1, text.len(), " \\\n  more\n"),
+      (0, text.len(), "RUN x"),
+      (0, 10, "# escape=`\n"),
+    ];
+    let previous = Dockerfile::parse(text).unwrap();
+    for (start, end, new_text) in edits {
+      let edit = TextEdit {
+        range: start..end,
+        new_text: new_text.to_string(),
+      };
+      let expected = Dockerfile::parse(&edit.apply(text)).unwrap();
+      assert_eq!(previous.reparse(&edit).unwrap(), expected, "{edit:?}");
+    }
+  }
+
+  #[test]
+  fn reparse_reuses_instructions_after_edit() {
+    let previous = Dockerfile::parse("RUN a\nRUN b\nRUN c\n").unwrap();
+    let edit = TextEdit {
+      range: 4..5,
+      new_text: "abc".to_string(),
+    };
+    let file = previous.reparse(&edit).unwrap();
+    assert_eq!(file.content, "RUN abc\nRUN b\nRUN c\n");
+    assert_eq!(file.instructions.len(), 3);
+    assert_eq!(file.instructions[2].span(), Span::new(14, 19));
+    assert_eq!(file.line_starts, vec![0, 8, 14, 20]);
+  }
+
   #[test]
   fn unquotes_unicode_escapes() {
     assert_eq!(unquote(r#""café""#), "café");
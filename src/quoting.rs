@@ -0,0 +1,223 @@
+//! Decoding and re-quoting the values of `ENV`, `LABEL` and `ARG`
+//! instructions, following the quoting rules Docker applies to them.
+
+use crate::configuration::QuoteStyle;
+
+/// A character of a decoded value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ValueChar {
+  c: char,
+  /// Whether the character is part of a variable reference (ex. `$HOME` or
+  /// `${A:-b}`), which has to be written as is, outside single quotes.
+  is_expansion: bool,
+}
+
+/// How a value is quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quote {
+  None,
+  Single,
+  Double,
+}
+
+/// Rewrites the raw text of a value in `style`. Returns `None` when it should
+/// be printed as is: for `maintain`, or when the value can't be decoded (ex. an
+/// unterminated quote or a variable reference with quotes or spaces in it).
+pub(crate) fn requote_value(raw: &str, escape: char, style: QuoteStyle) -> Option<String> {
+  let value = decode(raw, escape)?;
+  let quote = match style {
+    QuoteStyle::Maintain => return None,
+    QuoteStyle::AlwaysDouble => Quote::Double,
+    QuoteStyle::PreferDouble if raw.starts_with(['"', '\'']) || needs_quotes(&value, escape) => Quote::Double,
+    QuoteStyle::PreferDouble => Quote::None,
+    QuoteStyle::Minimal if !needs_quotes(&value, escape) => Quote::None,
+    QuoteStyle::Minimal => {
+      // single quotes when they avoid escapes that double quotes would need
+      let can_use_single = value.iter().all(|value_char| !value_char.is_expansion && value_char.c != '\'');
+      let double_escapes = value.iter().filter(|value_char| needs_double_quote_escape(**value_char, escape)).count();
+      if can_use_single && double_escapes > 0 { Quote::Single } else { Quote::Double }
+    }
+  };
+  Some(encode(&value, quote, escape))
+}
+
+/// The value a raw `ENV`, `LABEL` or `ARG` value text stands for, written in a
+/// single canonical form, so that differently quoted values can be compared.
+pub(crate) fn canonical_value(raw: &str, escape: char) -> String {
+  match decode(raw, escape) {
+    Some(value) => encode(&value, Quote::Double, escape),
+    None => raw.to_string(),
+  }
+}
+
+fn decode(raw: &str, escape: char) -> Option<Vec<ValueChar>> {
+  let literal = |c| ValueChar { c, is_expansion: false };
+  let expansion = |c| ValueChar { c, is_expansion: true };
+  let mut value = Vec::with_capacity(raw.len());
+  // the start and end of the unbraced variable references (ex. `$HOME`)
+  let mut names = Vec::new();
+  let mut quote = Quote::None;
+  let mut chars = raw.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (Quote::None, '\'') => quote = Quote::Single,
+      (Quote::None, '"') => quote = Quote::Double,
+      (Quote::Single, '\'') | (Quote::Double, '"') => quote = Quote::None,
+      (Quote::Single, c) => value.push(literal(c)),
+      (Quote::None, c) if c == escape => value.push(literal(chars.next()?)),
+      (Quote::Double, c) if c == escape => match chars.next()? {
+        next if next == '"' || next == '$' || next == escape => value.push(literal(next)),
+        next => {
+          value.push(literal(escape));
+          value.push(literal(next));
+        }
+      },
+      (_, '$') => {
+        let start = value.len();
+        value.push(expansion(c));
+        if chars.next_if_eq(&'{').is_some() {
+          value.push(expansion('{'));
+          loop {
+            let c = chars.next()?;
+            if c.is_whitespace() || c == '"' || c == '\'' || c == escape {
+              return None;
+            }
+            value.push(expansion(c));
+            if c == '}' {
+              break;
+            }
+          }
+        } else {
+          while let Some(c) = chars.next_if(|next| extends_name(&value[start + 1..], *next)) {
+            value.push(expansion(c));
+          }
+          names.push((start, value.len()));
+        }
+      }
+      (_, c) if c == '\n' || c == '\r' => return None,
+      (_, c) => value.push(literal(c)),
+    }
+  }
+  if quote != Quote::None {
+    return None;
+  }
+
+  // a name followed by literal text that could continue it (ex. the `x` of
+  // `$A"x"`) is braced, since it would merge with it once requoted
+  for (start, end) in names.into_iter().rev() {
+    if value
+      .get(end)
+      .is_some_and(|next| !next.is_expansion && extends_name(&value[start + 1..end], next.c))
+    {
+      if start + 1 == end {
+        // a lone `$` is literal and can't be braced
+        return None;
+      }
+      value.insert(end, expansion('}'));
+      value.insert(start + 1, expansion('{'));
+    }
+  }
+  Some(value)
+}
+
+/// Whether `c` continues an unbraced variable name that has read `name` so
+/// far, following Docker: a run of digits, a single special parameter (ex.
+/// `$?`), or letters, digits and underscores.
+fn extends_name(name: &[ValueChar], c: char) -> bool {
+  match name.first() {
+    None => c.is_alphanumeric() || c == '_' || c == '{' || matches!(c, '@' | '*' | '#' | '?' | '$' | '!' | '-'),
+    Some(first) if first.c.is_numeric() => c.is_numeric(),
+    Some(first) if !(first.c.is_alphabetic() || first.c == '_') => false,
+    Some(_) => c.is_alphanumeric() || c == '_',
+  }
+}
+
+fn needs_quotes(value: &[ValueChar], escape: char) -> bool {
+  value.is_empty()
+    || value
+      .iter()
+      .any(|value_char| !value_char.is_expansion && (value_char.c.is_whitespace() || matches!(value_char.c, '"' | '\'' | '$') || value_char.c == escape))
+}
+
+fn needs_double_quote_escape(value_char: ValueChar, escape: char) -> bool {
+  !value_char.is_expansion && (value_char.c == '"' || value_char.c == '$' || value_char.c == escape)
+}
+
+fn encode(value: &[ValueChar], quote: Quote, escape: char) -> String {
+  let mut result = String::with_capacity(value.len() + 2);
+  match quote {
+    Quote::None => result.extend(value.iter().map(|value_char| value_char.c)),
+    Quote::Single => {
+      result.push('\'');
+      result.extend(value.iter().map(|value_char| value_char.c));
+      result.push('\'');
+    }
+    Quote::Double => {
+      result.push('"');
+      for value_char in value {
+        if needs_double_quote_escape(*value_char, escape) {
+          result.push(escape);
+        }
+        result.push(value_char.c);
+      }
+      result.push('"');
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn requote(raw: &str, style: QuoteStyle) -> Option<String> {
+    requote_value(raw, '\\', style)
+  }
+
+  #[test]
+  fn requotes_values() {
+    assert_eq!(requote("'x'", QuoteStyle::Maintain), None);
+    assert_eq!(requote("'x'", QuoteStyle::AlwaysDouble).unwrap(), "\"x\"");
+    assert_eq!(requote("x", QuoteStyle::AlwaysDouble).unwrap(), "\"x\"");
+    assert_eq!(requote("x", QuoteStyle::PreferDouble).unwrap(), "x");
+    assert_eq!(requote("'x'", QuoteStyle::PreferDouble).unwrap(), "\"x\"");
+    assert_eq!(requote("a\\ b", QuoteStyle::PreferDouble).unwrap(), "\"a b\"");
+    assert_eq!(requote("\"x\"", QuoteStyle::Minimal).unwrap(), "x");
+    assert_eq!(requote("\"\"", QuoteStyle::Minimal).unwrap(), "\"\"");
+    assert_eq!(requote("'a b'", QuoteStyle::Minimal).unwrap(), "\"a b\"");
+  }
+
+  #[test]
+  fn escapes_literal_dollars_and_quotes() {
+    assert_eq!(requote("'$HOME'", QuoteStyle::PreferDouble).unwrap(), "\"\\$HOME\"");
+    assert_eq!(requote("$HOME/bin", QuoteStyle::AlwaysDouble).unwrap(), "\"$HOME/bin\"");
+    assert_eq!(requote("\"${A:-b}\"", QuoteStyle::Minimal).unwrap(), "${A:-b}");
+    assert_eq!(requote("'say \"hi\"'", QuoteStyle::AlwaysDouble).unwrap(), "\"say \\\"hi\\\"\"");
+    assert_eq!(requote("\"say \\\"hi\\\"\"", QuoteStyle::Minimal).unwrap(), "'say \"hi\"'");
+    assert_eq!(requote("\"it's $A\"", QuoteStyle::Minimal).unwrap(), "\"it's $A\"");
+    // only `"`, `$` and the escape character are escaped in double quotes
+    assert_eq!(requote("\"a\\nb\"", QuoteStyle::Minimal).unwrap(), "'a\\nb'");
+    assert_eq!(requote_value("\"C:\\dir\"", '`', QuoteStyle::Minimal).unwrap(), "C:\\dir");
+  }
+
+  #[test]
+  fn keeps_values_it_cannot_decode() {
+    assert_eq!(requote("'x", QuoteStyle::AlwaysDouble), None);
+    assert_eq!(requote("${A:-a b}", QuoteStyle::AlwaysDouble), None);
+    assert_eq!(canonical_value("'x", '\\'), "'x");
+    assert_eq!(canonical_value("'$x'", '\\'), canonical_value("\\$x", '\\'));
+  }
+
+  #[test]
+  fn braces_names_followed_by_name_characters() {
+    for raw in ["$V\"x\"", "$V'x'", "$V\\x"] {
+      assert_eq!(requote(raw, QuoteStyle::Minimal).unwrap(), "${V}x", "{}", raw);
+      assert_eq!(requote(raw, QuoteStyle::PreferDouble).unwrap(), "${V}x", "{}", raw);
+      assert_eq!(requote(raw, QuoteStyle::AlwaysDouble).unwrap(), "\"${V}x\"", "{}", raw);
+    }
+    assert_eq!(requote("$1'2'", QuoteStyle::Minimal).unwrap(), "${1}2");
+    assert_eq!(requote("$V'-x'", QuoteStyle::Minimal).unwrap(), "$V-x");
+    assert_eq!(requote("$?'x'", QuoteStyle::Minimal).unwrap(), "$?x");
+    assert_eq!(requote("$'x'", QuoteStyle::Minimal), None);
+  }
+}
@@ -0,0 +1,541 @@
+use crate::ast::*;
+use crate::configuration::CommandForm;
+use crate::configuration::Configuration;
+use crate::configuration::QuoteStyle;
+use crate::error::FormatError;
+use crate::exec_form::SHELL_ONLY_COMMANDS;
+use crate::healthcheck::check_option;
+use crate::quoting::canonical_value;
+use crate::shell::ShellTokenKind;
+use crate::shell::replace_backticks;
+use crate::shell::tokenize;
+
+/// Checks that the formatted text holds the same instructions as the original,
+/// comparing their semantic form so that changes to whitespace, line
+/// continuations, keyword case and comments are allowed. Other rewrites are
+/// only allowed when `config` enables the option that makes them.
+pub(crate) fn verify_semantics(original: &Dockerfile, formatted_text: &str, config: &Configuration) -> Result<(), FormatError> {
+  let formatted = Dockerfile::parse(formatted_text)?;
+  let (before, after) = (semantic_form(original, config), semantic_form(&formatted, config));
+  let changed = (0..before.len().max(after.len())).find(|i| before.get(*i) != after.get(*i));
+  match changed {
+    None => Ok(()),
+    Some(index) => {
+      let line = match original.instructions.get(index) {
+        Some(instruction) => original.line_col(instruction.span().start).0 + 1,
+        None => original.line_col(original.content.len()).0 + 1,
+      };
+      let display = |tokens: Option<&Vec<String>>| tokens.map(|tokens| tokens.join(" ")).unwrap_or_else(|| "<none>".to_string());
+      Err(FormatError::SemanticChange {
+        line,
+        before: display(before.get(index)),
+        after: display(after.get(index)),
+      })
+    }
+  }
+}
+
+/// The tokens that make up each instruction's meaning: its keyword, flags,
+/// exec array elements and shell words.
+fn semantic_form(file: &Dockerfile, config: &Configuration) -> Vec<Vec<String>> {
+  let stage_commands = stage_commands(file, config);
+  file
+    .instructions
+    .iter()
+    .zip(stage_commands)
+    .map(|(instruction, stage_command)| match exec_form_command(instruction, stage_command, config) {
+      Some(tokens) => tokens,
+      None => {
+        let mut tokens = Vec::new();
+        instruction_tokens(instruction, file, config, &mut tokens);
+        tokens
+      }
+    })
+    .collect()
+}
+
+/// The tokens of a `CMD` or `ENTRYPOINT` when `cmdForm` or `entrypointForm`
+/// can rewrite it in exec form: the arguments it runs, which are the same in
+/// either form for a command that runs the same without `/bin/sh -c`, and the
+/// command its stage runs, which catches a rewrite that changes how it
+/// combines with the stage's other `CMD` or `ENTRYPOINT`.
+fn exec_form_command(instruction: &Instruction, stage_command: Option<Vec<String>>, config: &Configuration) -> Option<Vec<String>> {
+  let (keyword, expr, form) = match instruction {
+    Instruction::Cmd(node) => ("CMD", &node.expr, config.cmd_form),
+    Instruction::Entrypoint(node) => ("ENTRYPOINT", &node.expr, config.entrypoint_form),
+    _ => return None,
+  };
+  if form != CommandForm::Exec {
+    return None;
+  }
+  let arguments = without_default_shell(command_arguments(expr, &default_shell(), config));
+  Some(vec![
+    keyword.to_string(),
+    format!("{:?}", arguments),
+    format!("(the stage runs {:?})", stage_command.unwrap_or_default()),
+  ])
+}
+
+/// The command each stage runs when a container starts, from its `SHELL`,
+/// `ENTRYPOINT` and `CMD` and those of the stage it's built on, given for each
+/// `CMD` and `ENTRYPOINT` instruction.
+fn stage_commands(file: &Dockerfile, config: &Configuration) -> Vec<Option<Vec<String>>> {
+  #[derive(Clone)]
+  struct Stage {
+    shell: Vec<String>,
+    /// The arguments, and whether it's in shell form, which ignores `CMD`.
+    entrypoint: Option<(Vec<String>, bool)>,
+    cmd: Option<Vec<String>>,
+    sets_cmd: bool,
+  }
+  impl Stage {
+    fn command(&self) -> Vec<String> {
+      let command = match &self.entrypoint {
+        Some((entrypoint, true)) => entrypoint.clone(),
+        Some((entrypoint, false)) => entrypoint.iter().chain(self.cmd.iter().flatten()).cloned().collect(),
+        None => self.cmd.clone().unwrap_or_default(),
+      };
+      without_default_shell(command)
+    }
+  }
+
+  let new_stage = || Stage {
+    shell: default_shell(),
+    entrypoint: None,
+    cmd: None,
+    sets_cmd: false,
+  };
+  let mut commands = vec![None; file.instructions.len()];
+  let mut aliases: Vec<(&str, Stage)> = Vec::new();
+  let mut stage = new_stage();
+  let mut stage_start = 0;
+  let mut alias: Option<&str> = None;
+  for index in 0..=file.instructions.len() {
+    let instruction = file.instructions.get(index);
+    if index == file.instructions.len() || matches!(instruction, Some(Instruction::From(_))) {
+      // the stage ends here, and only the stages after it can build on it
+      let command = stage.command();
+      for stage_command in commands[stage_start..index].iter_mut().flatten() {
+        *stage_command = command.clone();
+      }
+      aliases.extend(alias.take().map(|alias| (alias, stage.clone())));
+    }
+    match instruction {
+      Some(Instruction::From(node)) => {
+        stage = match aliases.iter().rev().find(|(alias, _)| alias.eq_ignore_ascii_case(&node.image.content)) {
+          Some((_, base)) => Stage {
+            sets_cmd: false,
+            ..base.clone()
+          },
+          None => new_stage(),
+        };
+        stage_start = index;
+        alias = node.alias.as_ref().map(|alias| alias.content.as_str());
+      }
+      Some(Instruction::Shell(node)) => {
+        if let ShellOrExecExpr::Exec(array) = &node.expr {
+          stage.shell = array.elements.iter().map(|element| element.content.clone()).collect();
+        }
+      }
+      Some(Instruction::Entrypoint(node)) => {
+        let is_shell = matches!(node.expr, ShellOrExecExpr::Shell(_));
+        stage.entrypoint = Some((command_arguments(&node.expr, &stage.shell, config), is_shell));
+        // an ENTRYPOINT resets the CMD the stage inherits
+        if !stage.sets_cmd {
+          stage.cmd = None;
+        }
+        commands[index] = Some(Vec::new());
+      }
+      Some(Instruction::Cmd(node)) => {
+        stage.cmd = Some(command_arguments(&node.expr, &stage.shell, config));
+        stage.sets_cmd = true;
+        commands[index] = Some(Vec::new());
+      }
+      _ => {}
+    }
+  }
+  commands
+}
+
+fn default_shell() -> Vec<String> {
+  vec!["/bin/sh".to_string(), "-c".to_string()]
+}
+
+/// The arguments a command runs: an exec array's elements, or the shell
+/// followed by the command's shell words.
+fn command_arguments(expr: &ShellOrExecExpr, shell: &[String], config: &Configuration) -> Vec<String> {
+  match expr {
+    ShellOrExecExpr::Exec(array) => array.elements.iter().map(|element| element.content.clone()).collect(),
+    ShellOrExecExpr::Shell(value) => {
+      let mut words = Vec::new();
+      shell_tokens(value, config, &mut words);
+      shell.iter().cloned().chain([words.join(" ")]).collect()
+    }
+  }
+}
+
+/// Replaces `/bin/sh -c <command>` with the words of the command when it runs
+/// the same without the shell.
+fn without_default_shell(arguments: Vec<String>) -> Vec<String> {
+  match arguments.as_slice() {
+    [shell, flag, command] if *shell == "/bin/sh" && flag == "-c" => plain_words(command).unwrap_or(arguments),
+    _ => arguments,
+  }
+}
+
+/// The words of a shell command that's a plain list of words, which the shell
+/// passes on unchanged once their quotes and escapes are removed, or `None` if
+/// it uses any other shell feature.
+fn plain_words(command: &str) -> Option<Vec<String>> {
+  let mut words = Vec::new();
+  let mut word: Option<String> = None;
+  let mut quote = None;
+  let mut chars = command.chars();
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (None, ' ') => words.extend(word.take()),
+      (None, '\\') => word.get_or_insert_with(String::new).push(chars.next()?),
+      (None, '\'' | '"') => {
+        quote = Some(c);
+        word.get_or_insert_with(String::new);
+      }
+      (Some(q), c) if c == q => quote = None,
+      (Some('"'), '\\') => {
+        let next = chars.next()?;
+        let word = word.get_or_insert_with(String::new);
+        if !matches!(next, '"' | '\\' | '$' | '`') {
+          word.push('\\');
+        }
+        word.push(next);
+      }
+      (Some('"'), '$' | '`') => return None,
+      (None, '$' | '`' | '*' | '?' | '[' | '~' | '{' | '}' | '|' | '&' | ';' | '<' | '>' | '(' | ')' | '#') => return None,
+      // an assignment before the command
+      (None, '=') if words.is_empty() => return None,
+      (_, c) => word.get_or_insert_with(String::new).push(c),
+    }
+  }
+  words.extend(word);
+  match words.first() {
+    Some(command) if quote.is_none() && !SHELL_ONLY_COMMANDS.contains(&command.as_str()) => Some(words),
+    _ => None,
+  }
+}
+
+fn instruction_tokens(instruction: &Instruction, file: &Dockerfile, config: &Configuration, tokens: &mut Vec<String>) {
+  // values are compared by what they stand for when quoteStyle can requote
+  // them, and as written otherwise. an ENV value with a space is always
+  // double-quoted, so it's compared that way too
+  let value = |span: &Span, is_env: bool| {
+    let raw = &file.content[span.start..span.end];
+    match config.quote_style != QuoteStyle::Maintain || (is_env && raw.contains(' ')) {
+      true => canonical_value(raw, file.escape),
+      false => raw.to_string(),
+    }
+  };
+  let mut keyword = |keyword: &str| tokens.push(keyword.to_string());
+  match instruction {
+    Instruction::From(node) => {
+      keyword("FROM");
+      flag_tokens(node.flags.iter().map(|flag| (&flag.name, flag.value.content.clone())), tokens);
+      tokens.push(node.image.content.clone());
+      if let Some(alias) = &node.alias {
+        tokens.push("AS".to_string());
+        tokens.push(alias.content.clone());
+      }
+    }
+    Instruction::Arg(node) => {
+      keyword("ARG");
+      tokens.push(node.name.content.clone());
+      if let Some(arg_value) = &node.value {
+        tokens.push(format!("={}", value(&arg_value.span, false)));
+      }
+    }
+    Instruction::Label(node) => {
+      keyword("LABEL");
+      tokens.extend(
+        node
+          .labels
+          .iter()
+          .map(|label| format!("{}={}", label.name.content, value(&label.value.span, false))),
+      );
+    }
+    Instruction::Run(node) => expr_tokens("RUN", &node.expr, config, tokens),
+    Instruction::Entrypoint(node) => expr_tokens("ENTRYPOINT", &node.expr, config, tokens),
+    Instruction::Cmd(node) => expr_tokens("CMD", &node.expr, config, tokens),
+    Instruction::Shell(node) => expr_tokens("SHELL", &node.expr, config, tokens),
+    Instruction::Copy(node) => copy_tokens("COPY", &node.flags, &node.args, tokens),
+    Instruction::Add(node) => copy_tokens("ADD", &node.flags, &node.args, tokens),
+    Instruction::Env(node) => {
+      keyword("ENV");
+      for var in &node.vars {
+        match var.value.components.as_slice() {
+          [BreakableStringComponent::String(var_value)] => tokens.push(format!("{}={}", var.key.content, value(&var_value.span, true))),
+          _ => {
+            tokens.push(format!("{}=", var.key.content));
+            shell_tokens(&var.value, config, tokens);
+          }
+        }
+      }
+    }
+    Instruction::Onbuild(node) => {
+      keyword("ONBUILD");
+      instruction_tokens(&node.instruction, file, config, tokens);
+    }
+    Instruction::Healthcheck(node) => {
+      keyword("HEALTHCHECK");
+      // option values are compared in their normal form when
+      // normalizeHealthcheckOptions rewrites them
+      let flags = node.flags.iter().map(|flag| {
+        let value = match check_option(&flag.name.content, &flag.value.content) {
+          Ok(value) if config.normalize_healthcheck_options => value,
+          _ => flag.value.content.clone(),
+        };
+        (&flag.name, value)
+      });
+      flag_tokens(flags, tokens);
+      match &node.cmd {
+        Some(cmd) => instruction_tokens(cmd, file, config, tokens),
+        None => tokens.push("NONE".to_string()),
+      }
+    }
+    Instruction::Heredoc(node) => {
+      instruction_tokens(&node.instruction, file, config, tokens);
+      tokens.push(node.body.clone());
+    }
+    Instruction::Misc(node) => {
+      keyword(&node.instruction.content.to_uppercase());
+      shell_tokens(&node.arguments, config, tokens);
+    }
+    Instruction::Unknown(node) => split_words(&node.content, file.escape, tokens),
+  }
+}
+
+fn copy_tokens(keyword: &str, flags: &[CopyFlag], args: &CopyArgs, tokens: &mut Vec<String>) {
+  tokens.push(keyword.to_string());
+  flag_tokens(flags.iter().map(|flag| (&flag.name, flag.value.content.clone())), tokens);
+  match args {
+    CopyArgs::Paths { sources, destination } => {
+      tokens.extend(sources.iter().map(|source| source.content.clone()));
+      tokens.push(destination.content.clone());
+    }
+    CopyArgs::Exec(array) => tokens.push(array_token(array)),
+  }
+}
+
+fn expr_tokens(keyword: &str, expr: &ShellOrExecExpr, config: &Configuration, tokens: &mut Vec<String>) {
+  tokens.push(keyword.to_string());
+  match expr {
+    ShellOrExecExpr::Shell(value) => shell_tokens(value, config, tokens),
+    ShellOrExecExpr::Exec(array) => tokens.push(array_token(array)),
+  }
+}
+
+/// The flags of an instruction sorted by name, since their order doesn't
+/// matter. Repeated flags keep their relative order, which does.
+fn flag_tokens<'a>(flags: impl Iterator<Item = (&'a SpannedString, String)>, tokens: &mut Vec<String>) {
+  let mut flags = flags.collect::<Vec<_>>();
+  flags.sort_by_key(|(name, _)| &name.content);
+  tokens.extend(flags.into_iter().map(|(name, value)| format!("--{}={}", name.content, value)));
+}
+
+/// An exec array as a single token, so it never equals the same words in shell
+/// form, except that [`exec_form_command`] compares the arguments they run.
+fn array_token(array: &StringArray) -> String {
+  format!("{:?}", array.elements.iter().map(|element| &element.content).collect::<Vec<_>>())
+}
+
+/// The words of a shell command as the shell splits them, with operators and
+/// redirections as separate tokens. The shell ends a word at an operator, so
+/// `a;b`, `a ; b` and `a; b` are the same command and the spacing around
+/// operators (which `shellFormatting` changes) doesn't count as a change.
+/// With `shellFormatting`, command substitutions are compared in `$( )` form
+/// since it rewrites backticks. Comments, including those between continued
+/// lines, aren't part of the command.
+fn shell_tokens(value: &BreakableString, config: &Configuration, tokens: &mut Vec<String>) {
+  let mut word = String::new();
+  for token in tokenize(value) {
+    let is_word_part = matches!(
+      token.kind,
+      ShellTokenKind::Word | ShellTokenKind::SingleQuoted | ShellTokenKind::DoubleQuoted | ShellTokenKind::Expansion
+    );
+    if !is_word_part && !word.is_empty() {
+      tokens.push(std::mem::take(&mut word));
+    }
+    match token.kind {
+      ShellTokenKind::Whitespace | ShellTokenKind::Comment => {}
+      ShellTokenKind::Operator | ShellTokenKind::Redirection => tokens.push(token.text),
+      ShellTokenKind::DoubleQuoted | ShellTokenKind::Expansion if config.shell_formatting => {
+        word.push_str(&replace_backticks(&token.text).unwrap_or(token.text))
+      }
+      _ => word.push_str(&token.text),
+    }
+  }
+  if !word.is_empty() {
+    tokens.push(word);
+  }
+}
+
+/// Splits text into words at whitespace outside of quotes, dropping line
+/// continuations.
+fn split_words(text: &str, escape: char, tokens: &mut Vec<String>) {
+  let mut word = String::new();
+  let mut quote = None;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == escape && quote != Some('\'') {
+      // a continuation (escape, optional trailing whitespace, newline) joins
+      // the lines; any other escaped character is kept with its escape
+      let mut lookahead = chars.clone();
+      while lookahead.next_if(|c| *c == ' ' || *c == '\t' || *c == '\r').is_some() {}
+      if lookahead.peek() == Some(&'\n') {
+        lookahead.next();
+        chars = lookahead;
+        if !word.is_empty() && quote.is_none() {
+          tokens.push(std::mem::take(&mut word));
+        }
+        continue;
+      }
+      word.push(c);
+      if let Some(next) = chars.next() {
+        word.push(next);
+      }
+      continue;
+    }
+    match quote {
+      Some(q) if c == q => quote = None,
+      Some(_) => {}
+      None if c == '"' || c == '\'' => quote = Some(c),
+      None if c.is_whitespace() => {
+        if !word.is_empty() {
+          tokens.push(std::mem::take(&mut word));
+        }
+        continue;
+      }
+      None => {}
+    }
+    word.push(c);
+  }
+  if !word.is_empty() {
+    tokens.push(word);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::configuration::ConfigurationBuilder;
+
+  fn words(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    split_words(text, '\\', &mut tokens);
+    tokens
+  }
+
+  #[test]
+  fn splits_shell_words() {
+    assert_eq!(
+      words("apt-get   install \\\n    curl \\  \n&& echo"),
+      ["apt-get", "install", "curl", "&&", "echo"]
+    );
+    assert_eq!(words("echo \"a  b\" 'c  d' e\\ f"), ["echo", "\"a  b\"", "'c  d'", "e\\ f"]);
+  }
+
+  #[test]
+  fn allows_formatting_changes() {
+    let config = ConfigurationBuilder::new().build();
+    let original = Dockerfile::parse("from alpine\nRUN   apt-get install \\\n  # a comment\n    curl\nCMD [ \"a\",\"b\" ]\n").unwrap();
+    assert!(verify_semantics(&original, "FROM alpine\nRUN apt-get install curl\nCMD [\"a\", \"b\"]\n", &config).is_ok());
+    let original = Dockerfile::parse("RUN a&&b>/dev/null;c").unwrap();
+    assert!(verify_semantics(&original, "RUN a && b > /dev/null ; c", &config).is_ok());
+    let original = Dockerfile::parse("COPY --link=true --from=a x /y").unwrap();
+    assert!(verify_semantics(&original, "COPY --from=a --link=true x /y", &config).is_ok());
+  }
+
+  #[test]
+  fn compares_values_as_written_unless_requoted() {
+    let original = Dockerfile::parse("LABEL a='x' b=\"y z\"\nENV A='x'").unwrap();
+    let formatted = "LABEL a=x b='y z'\nENV A=\"x\"";
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().build()).is_err());
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().quote_style(QuoteStyle::Minimal).build()).is_ok());
+    // an ENV value with a space is double-quoted whatever the quoteStyle
+    let original = Dockerfile::parse("ENV A='x y'").unwrap();
+    assert!(verify_semantics(&original, "ENV A=\"x y\"", &ConfigurationBuilder::new().build()).is_ok());
+  }
+
+  #[test]
+  fn compares_normalized_healthcheck_options_when_enabled() {
+    let original = Dockerfile::parse("HEALTHCHECK --interval=90s CMD a").unwrap();
+    let formatted = "HEALTHCHECK --interval=1m30s CMD a";
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().build()).is_err());
+    let config = ConfigurationBuilder::new().normalize_healthcheck_options(true).build();
+    assert!(verify_semantics(&original, formatted, &config).is_ok());
+  }
+
+  #[test]
+  fn compares_exec_form_rewrites_by_what_they_run() {
+    let original = Dockerfile::parse("CMD node 'app.js'").unwrap();
+    let formatted = "CMD [\"node\", \"app.js\"]";
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().build()).is_err());
+    let config = ConfigurationBuilder::new()
+      .cmd_form(CommandForm::Exec)
+      .entrypoint_form(CommandForm::Exec)
+      .build();
+    assert!(verify_semantics(&original, formatted, &config).is_ok());
+    // the words are compared after the shell removes their quotes
+    assert!(verify_semantics(&original, "CMD [\"node\", \"'app.js'\"]", &config).is_err());
+    let original = Dockerfile::parse("CMD node $APP").unwrap();
+    assert!(verify_semantics(&original, "CMD [\"node\", \"$APP\"]", &config).is_err());
+    let original = Dockerfile::parse("SHELL [\"/bin/bash\", \"-c\"]\nCMD node app.js").unwrap();
+    assert!(verify_semantics(&original, "SHELL [\"/bin/bash\", \"-c\"]\nCMD [\"node\", \"app.js\"]", &config).is_err());
+  }
+
+  #[test]
+  fn compares_the_command_each_stage_runs() {
+    let config = ConfigurationBuilder::new()
+      .cmd_form(CommandForm::Exec)
+      .entrypoint_form(CommandForm::Exec)
+      .build();
+    let original = Dockerfile::parse("FROM a\nENTRYPOINT server --verbose\nCMD [\"--port\", \"80\"]").unwrap();
+    assert!(matches!(
+      verify_semantics(&original, "FROM a\nENTRYPOINT [\"server\", \"--verbose\"]\nCMD [\"--port\", \"80\"]", &config),
+      Err(FormatError::SemanticChange { line: 2, .. })
+    ));
+    let original = Dockerfile::parse("FROM a AS base\nENTRYPOINT [\"/x\"]\nFROM base\nCMD node app.js").unwrap();
+    assert!(matches!(
+      verify_semantics(&original, "FROM a AS base\nENTRYPOINT [\"/x\"]\nFROM base\nCMD [\"node\", \"app.js\"]", &config),
+      Err(FormatError::SemanticChange { line: 4, .. })
+    ));
+    let original = Dockerfile::parse("FROM a AS base\nENTRYPOINT [\"/x\"]\nFROM b\nCMD node app.js").unwrap();
+    assert!(verify_semantics(&original, "FROM a AS base\nENTRYPOINT [\"/x\"]\nFROM b\nCMD [\"node\", \"app.js\"]", &config).is_ok());
+  }
+
+  #[test]
+  fn reports_changed_instructions() {
+    let config = ConfigurationBuilder::new().build();
+    let original = Dockerfile::parse("FROM alpine\nRUN echo \"a  b\"\n").unwrap();
+    let err = verify_semantics(&original, "FROM alpine\nRUN echo \"a b\"\n", &config).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "formatting changed the meaning of the instruction on line 2, so the file was left unformatted (please report this as a bug)\n  before: RUN echo \"a  b\"\n  after:  RUN echo \"a b\""
+    );
+    assert!(matches!(
+      verify_semantics(&original, "FROM alpine\nRUN [\"echo\", \"a  b\"]\n", &config),
+      Err(FormatError::SemanticChange { line: 2, .. })
+    ));
+    assert!(matches!(
+      verify_semantics(&original, "FROM alpine\n", &config),
+      Err(FormatError::SemanticChange { line: 2, .. })
+    ));
+    let original = Dockerfile::parse("COPY --chown=a --chown=b x /y").unwrap();
+    assert!(verify_semantics(&original, "COPY --chown=b --chown=a x /y", &config).is_err());
+  }
+
+  #[test]
+  fn compares_command_substitutions_with_shell_formatting() {
+    let original = Dockerfile::parse("RUN echo `date` \"`id`\"").unwrap();
+    let formatted = "RUN echo $(date) \"$(id)\"";
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().build()).is_err());
+    assert!(verify_semantics(&original, formatted, &ConfigurationBuilder::new().shell_formatting(true).build()).is_ok());
+  }
+}
@@ -0,0 +1,482 @@
+//! A lexer for the POSIX shell commands in shell-form instructions (ex. the
+//! body of `RUN apt-get update && apt-get install -y curl`).
+//!
+//! The lexer is lenient: it never fails, and a quote or expansion left open
+//! runs to the end of the command.
+
+use crate::ast::BreakableString;
+use crate::ast::BreakableStringComponent;
+use crate::ast::Span;
+
+/// The kind of a [`ShellToken`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellTokenKind {
+  /// Unquoted literal text, including any backslash escapes (ex. `apt-get`,
+  /// `a\ b`).
+  Word,
+  /// A `'single quoted'` string.
+  SingleQuoted,
+  /// A `"double quoted"` string, including any expansions within it.
+  DoubleQuoted,
+  /// A parameter expansion (`$VAR`, `${VAR:-default}`), command substitution
+  /// (`$(cmd)`, `` `cmd` ``) or arithmetic expansion (`$((1 + 2))`).
+  Expansion,
+  /// A control operator: `&&`, `||`, `;;`, `;`, `|`, `&`, `(` or `)`.
+  Operator,
+  /// A redirection operator along with its file descriptor, if any (ex. `>`,
+  /// `2>&`, `<<-`, `&>`).
+  Redirection,
+  /// A shell comment, which runs to the end of the command since Docker joins
+  /// continued lines, or a comment line between continued lines.
+  Comment,
+  /// A run of spaces or tabs on one line.
+  Whitespace,
+}
+
+/// A token of a shell command.
+///
+/// Tokens that aren't separated by [`ShellTokenKind::Whitespace`] or an
+/// operator are parts of the same shell word (ex. `--name="$NAME"` is a
+/// [`ShellTokenKind::Word`] followed by a [`ShellTokenKind::DoubleQuoted`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellToken {
+  pub kind: ShellTokenKind,
+  /// The token's span in the Dockerfile. It includes any line continuations
+  /// within the token.
+  pub span: Span,
+  /// The token's text with line continuations removed.
+  pub text: String,
+  /// Whether the token's quote or expansion is closed. `false` when it runs
+  /// to the end of the command.
+  pub terminated: bool,
+}
+
+/// Splits a shell-form instruction's command into tokens.
+pub fn tokenize(value: &BreakableString) -> Vec<ShellToken> {
+  let mut chars = Vec::new();
+  let mut comments = Vec::new();
+  let mut line_starts = Vec::new();
+  for component in &value.components {
+    match component {
+      BreakableStringComponent::String(text) => {
+        line_starts.push(chars.len());
+        chars.extend(text.content.char_indices().map(|(i, c)| (text.span.start + i, c)));
+      }
+      BreakableStringComponent::Comment(comment) => comments.push(ShellToken {
+        kind: ShellTokenKind::Comment,
+        span: comment.span,
+        text: comment.content.clone(),
+        terminated: true,
+      }),
+    }
+  }
+  let mut lexer = Lexer {
+    chars: &chars,
+    line_starts: &line_starts,
+    index: 0,
+    tokens: Vec::new(),
+    terminated: true,
+  };
+  let mut comments = comments.into_iter().peekable();
+  while lexer.index < chars.len() {
+    // comment lines go before the token that follows them
+    let start = chars[lexer.index].0;
+    while let Some(comment) = comments.next_if(|comment| comment.span.start < start) {
+      lexer.tokens.push(comment);
+    }
+    lexer.next_token();
+  }
+  lexer.tokens.extend(comments);
+  lexer.tokens
+}
+
+/// Rewrites the `` `cmd` `` command substitutions in the text of an
+/// [`ShellTokenKind::Expansion`] or [`ShellTokenKind::DoubleQuoted`] token as
+/// `$(cmd)`. Returns `None` if there are none or if they can't be rewritten
+/// as is, such as when the text has escapes, which mean something else
+/// between backticks.
+pub(crate) fn replace_backticks(text: &str) -> Option<String> {
+  if !text.contains('`') || text.contains('\\') {
+    return None;
+  }
+  let mut result = String::with_capacity(text.len() + 2);
+  let mut parts = text.split('`');
+  result.push_str(parts.next().unwrap());
+  let mut is_open = false;
+  for part in parts {
+    if is_open {
+      result.push(')');
+    } else if result.ends_with('$') || part.starts_with('(') {
+      // `$$(` and `$((` would be a different expansion
+      return None;
+    } else {
+      result.push_str("$(");
+    }
+    result.push_str(part);
+    is_open = !is_open;
+  }
+  (!is_open).then_some(result)
+}
+
+struct Lexer<'a> {
+  /// The characters of the command's lines along with their offsets in the
+  /// file, joined as Docker joins continued lines.
+  chars: &'a [(usize, char)],
+  /// The indexes in `chars` where each line starts.
+  line_starts: &'a [usize],
+  index: usize,
+  tokens: Vec<ShellToken>,
+  /// Whether every quote and expansion in the current token was closed.
+  terminated: bool,
+}
+
+impl Lexer<'_> {
+  fn peek(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.index + offset).map(|(_, c)| *c)
+  }
+
+  fn starts_with(&self, text: &str) -> bool {
+    text.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c))
+  }
+
+  fn next_token(&mut self) {
+    let start = self.index;
+    self.terminated = true;
+    let c = self.peek(0).unwrap();
+    let kind = match c {
+      ' ' | '\t' => {
+        // whitespace never spans lines so each line's indentation and
+        // trailing whitespace are separate tokens
+        self.index += 1;
+        while matches!(self.peek(0), Some(' ' | '\t')) && self.line_starts.binary_search(&self.index).is_err() {
+          self.index += 1;
+        }
+        ShellTokenKind::Whitespace
+      }
+      '#' if self.at_word_start() => {
+        self.index = self.chars.len();
+        ShellTokenKind::Comment
+      }
+      '\'' => {
+        self.index += 1;
+        self.skip_until_after('\'');
+        ShellTokenKind::SingleQuoted
+      }
+      '"' => {
+        self.index += 1;
+        self.skip_double_quoted();
+        ShellTokenKind::DoubleQuoted
+      }
+      '`' => {
+        self.index += 1;
+        self.skip_backticks();
+        ShellTokenKind::Expansion
+      }
+      '$' if self.skip_expansion() => ShellTokenKind::Expansion,
+      _ => {
+        if let Some(len) = self.redirection_len() {
+          self.index += len;
+          ShellTokenKind::Redirection
+        } else if let Some(len) = self.operator_len() {
+          self.index += len;
+          ShellTokenKind::Operator
+        } else {
+          self.skip_word();
+          ShellTokenKind::Word
+        }
+      }
+    };
+    let chars = &self.chars[start..self.index];
+    self.tokens.push(ShellToken {
+      kind,
+      span: Span::new(chars[0].0, chars.last().map(|(i, c)| i + c.len_utf8()).unwrap()),
+      text: chars.iter().map(|(_, c)| c).collect(),
+      terminated: self.terminated,
+    });
+  }
+
+  /// Whether the next token starts a new word, which is where a `#` starts a
+  /// comment.
+  fn at_word_start(&self) -> bool {
+    match self.tokens.last() {
+      Some(token) => matches!(
+        token.kind,
+        ShellTokenKind::Whitespace | ShellTokenKind::Operator | ShellTokenKind::Redirection | ShellTokenKind::Comment
+      ),
+      None => true,
+    }
+  }
+
+  fn skip_until_after(&mut self, end: char) {
+    while let Some(c) = self.peek(0) {
+      self.index += 1;
+      if c == end {
+        return;
+      }
+    }
+    self.terminated = false;
+  }
+
+  fn skip_double_quoted(&mut self) {
+    while let Some(c) = self.peek(0) {
+      match c {
+        '"' => {
+          self.index += 1;
+          return;
+        }
+        '\\' => self.index += 2,
+        '`' => {
+          self.index += 1;
+          self.skip_backticks();
+        }
+        '$' => {
+          if !self.skip_expansion() {
+            self.index += 1;
+          }
+        }
+        _ => self.index += 1,
+      }
+    }
+    self.index = self.index.min(self.chars.len());
+    self.terminated = false;
+  }
+
+  fn skip_backticks(&mut self) {
+    while let Some(c) = self.peek(0) {
+      self.index += if c == '\\' { 2 } else { 1 };
+      if c == '`' {
+        self.index = self.index.min(self.chars.len());
+        return;
+      }
+    }
+    self.index = self.index.min(self.chars.len());
+    self.terminated = false;
+  }
+
+  /// Skips an expansion starting at a `$`. Returns false if the `$` doesn't
+  /// start one (ex. a trailing `$`), in which case it's literal.
+  fn skip_expansion(&mut self) -> bool {
+    match self.peek(1) {
+      Some('{') => {
+        self.index += 2;
+        self.skip_nested('{', '}');
+      }
+      Some('(') => {
+        self.index += 2;
+        self.skip_nested('(', ')');
+      }
+      Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+        self.index += 2;
+        while matches!(self.peek(0), Some(c) if c == '_' || c.is_ascii_alphanumeric()) {
+          self.index += 1;
+        }
+      }
+      Some(c) if c.is_ascii_digit() || "@*#?-$!".contains(c) => self.index += 2,
+      _ => return false,
+    }
+    true
+  }
+
+  /// Skips to after the `close` matching an already consumed `open`, skipping
+  /// over quotes and escapes.
+  fn skip_nested(&mut self, open: char, close: char) {
+    let mut depth = 1;
+    while let Some(c) = self.peek(0) {
+      match c {
+        '\\' => self.index += 2,
+        '\'' => {
+          self.index += 1;
+          self.skip_until_after('\'');
+        }
+        '"' => {
+          self.index += 1;
+          self.skip_double_quoted();
+        }
+        _ => {
+          self.index += 1;
+          if c == open {
+            depth += 1;
+          } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+              return;
+            }
+          }
+        }
+      }
+    }
+    self.index = self.index.min(self.chars.len());
+    self.terminated = false;
+  }
+
+  /// The length of a redirection operator (with its file descriptor) at the
+  /// current position.
+  fn redirection_len(&self) -> Option<usize> {
+    let mut digits = 0;
+    if self.at_word_start() {
+      while matches!(self.peek(digits), Some(c) if c.is_ascii_digit()) {
+        digits += 1;
+      }
+    }
+    let rest = self.index + digits;
+    let rest_starts_with = |text: &str| text.chars().enumerate().all(|(i, c)| self.chars.get(rest + i).map(|(_, c)| *c) == Some(c));
+    let operators: &[&str] = if digits == 0 {
+      &["&>>", "&>", "<<-", "<<", "<>", "<&", ">>", ">&", ">|", "<", ">"]
+    } else {
+      &["<<-", "<<", "<>", "<&", ">>", ">&", ">|", "<", ">"]
+    };
+    operators
+      .iter()
+      .find(|operator| rest_starts_with(operator))
+      .map(|operator| digits + operator.len())
+  }
+
+  fn operator_len(&self) -> Option<usize> {
+    ["&&", "||", ";;", ";", "|", "&", "(", ")"]
+      .iter()
+      .find(|operator| self.starts_with(operator))
+      .map(|operator| operator.len())
+  }
+
+  fn skip_word(&mut self) {
+    while let Some(c) = self.peek(0) {
+      match c {
+        ' ' | '\t' | '\'' | '"' | '`' | '&' | '|' | ';' | '(' | ')' | '<' | '>' => break,
+        '$'
+          if self
+            .peek(1)
+            .is_some_and(|c| c == '{' || c == '(' || c == '_' || c.is_ascii_alphanumeric() || "@*#?-$!".contains(c)) =>
+        {
+          break;
+        }
+        '\\' => self.index = (self.index + 2).min(self.chars.len()),
+        _ => self.index += 1,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::ast::Dockerfile;
+  use crate::ast::Instruction;
+  use crate::ast::ShellOrExecExpr;
+
+  fn tokens(text: &str) -> Vec<(ShellTokenKind, String)> {
+    let file = Dockerfile::parse(text).unwrap();
+    let Instruction::Run(run) = &file.instructions[0] else {
+      panic!("expected RUN");
+    };
+    let ShellOrExecExpr::Shell(value) = &run.expr else {
+      panic!("expected shell form");
+    };
+    tokenize(value)
+      .into_iter()
+      .filter(|token| token.kind != ShellTokenKind::Whitespace)
+      .map(|token| (token.kind, token.text))
+      .collect()
+  }
+
+  use ShellTokenKind::*;
+
+  fn token(kind: ShellTokenKind, text: &str) -> (ShellTokenKind, String) {
+    (kind, text.to_string())
+  }
+
+  #[test]
+  fn tokenizes_words_operators_and_redirections() {
+    assert_eq!(
+      tokens("RUN apt-get update && make -j4 2>&1 | tee log >> out; cat <<-EOF\n"),
+      [
+        token(Word, "apt-get"),
+        token(Word, "update"),
+        token(Operator, "&&"),
+        token(Word, "make"),
+        token(Word, "-j4"),
+        token(Redirection, "2>&"),
+        token(Word, "1"),
+        token(Operator, "|"),
+        token(Word, "tee"),
+        token(Word, "log"),
+        token(Redirection, ">>"),
+        token(Word, "out"),
+        token(Operator, ";"),
+        token(Word, "cat"),
+        token(Redirection, "<<-"),
+        token(Word, "EOF"),
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenizes_quotes_and_expansions() {
+    assert_eq!(
+      tokens(r#"RUN echo "a  $(echo "b")" 'c  $d' --name=$NAME ${A:-"x y"} `date` $((1 + 2)) a\ b"#),
+      [
+        token(Word, "echo"),
+        token(DoubleQuoted, r#""a  $(echo "b")""#),
+        token(SingleQuoted, "'c  $d'"),
+        token(Word, "--name="),
+        token(Expansion, "$NAME"),
+        token(Expansion, r#"${A:-"x y"}"#),
+        token(Expansion, "`date`"),
+        token(Expansion, "$((1 + 2))"),
+        token(Word, r"a\ b"),
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenizes_comments() {
+    assert_eq!(
+      tokens("RUN a \\\n  # a comment line\n  && b # the rest \\\n  is a comment\n"),
+      [
+        token(Word, "a"),
+        token(Comment, "# a comment line"),
+        token(Operator, "&&"),
+        token(Word, "b"),
+        token(Comment, "# the rest   is a comment"),
+      ]
+    );
+    assert_eq!(tokens("RUN echo a#b\n"), [token(Word, "echo"), token(Word, "a#b")]);
+  }
+
+  #[test]
+  fn joins_tokens_across_continued_lines() {
+    let file = Dockerfile::parse("RUN echo \"a \\\n  b\" \\\n  c\n").unwrap();
+    let Instruction::Run(run) = &file.instructions[0] else { unreachable!() };
+    let ShellOrExecExpr::Shell(value) = &run.expr else { unreachable!() };
+    let tokens = tokenize(value);
+    assert_eq!(tokens[2].kind, DoubleQuoted);
+    assert_eq!(tokens[2].text, "\"a   b\"");
+    assert_eq!(tokens[2].span, Span::new(9, 18));
+    // whitespace is split at the end of each line
+    assert_eq!(
+      tokens[3..].iter().map(|token| (token.kind, token.span)).collect::<Vec<_>>(),
+      [(Whitespace, Span::new(18, 19)), (Whitespace, Span::new(21, 23)), (Word, Span::new(23, 24))]
+    );
+  }
+
+  #[test]
+  fn keeps_unterminated_quotes_to_the_end() {
+    assert_eq!(tokens("RUN echo \"a  b\n"), [token(Word, "echo"), token(DoubleQuoted, "\"a  b")]);
+    assert_eq!(tokens("RUN echo $\n"), [token(Word, "echo"), token(Word, "$")]);
+    for (text, terminated) in [
+      ("RUN 'a'", true),
+      ("RUN 'a", false),
+      ("RUN \"a\\\"\"", true),
+      ("RUN \"a\\\"", false),
+      ("RUN `a`", true),
+      ("RUN `a", false),
+      ("RUN $(a $(b))", true),
+      ("RUN $(a $(b)", false),
+      ("RUN ${a", false),
+    ] {
+      let file = Dockerfile::parse(text).unwrap();
+      let Instruction::Run(run) = &file.instructions[0] else { unreachable!() };
+      let ShellOrExecExpr::Shell(value) = &run.expr else { unreachable!() };
+      assert_eq!(tokenize(value)[0].terminated, terminated, "{}", text);
+    }
+  }
+}
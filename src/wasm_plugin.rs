@@ -13,6 +13,7 @@ use dprint_core::plugins::SyncHostFormatRequest;
 use dprint_core::plugins::SyncPluginHandler;
 
 use super::configuration::Configuration;
+use super::configuration::check_config_updates;
 use super::configuration::resolve_config;
 
 struct DockerfilePluginHandler;
@@ -21,12 +22,12 @@ impl SyncPluginHandler<Configuration> for DockerfilePluginHandler {
   fn resolve_config(&mut self, config: ConfigKeyMap, global_config: &GlobalConfiguration) -> PluginResolveConfigurationResult<Configuration> {
     let result = resolve_config(config, global_config);
     PluginResolveConfigurationResult {
-      config: result.config,
-      diagnostics: result.diagnostics,
       file_matching: FileMatchingInfo {
-        file_extensions: vec!["dockerfile".to_string()],
-        file_names: vec!["Dockerfile".to_string()],
+        file_extensions: result.config.file_extensions(),
+        file_names: result.config.file_names.clone(),
       },
+      config: result.config,
+      diagnostics: result.diagnostics,
     }
   }
 
@@ -42,8 +43,8 @@ impl SyncPluginHandler<Configuration> for DockerfilePluginHandler {
     }
   }
 
-  fn check_config_updates(&self, _message: CheckConfigUpdatesMessage) -> Result<Vec<ConfigChange>, FormatError> {
-    Ok(Vec::new())
+  fn check_config_updates(&self, message: CheckConfigUpdatesMessage) -> Result<Vec<ConfigChange>, FormatError> {
+    Ok(check_config_updates(&message))
   }
 
   fn license_text(&mut self) -> String {
@@ -52,6 +53,10 @@ impl SyncPluginHandler<Configuration> for DockerfilePluginHandler {
 
   fn format(&mut self, request: SyncFormatRequest<Configuration>, _format_with_host: impl FnMut(SyncHostFormatRequest) -> FormatResult) -> FormatResult {
     let file_text = String::from_utf8(request.file_bytes)?;
+    if let Some(range) = request.range {
+      let edit = super::format_range(&file_text, range, request.config).map_err(FormatError::new)?;
+      return Ok(edit.map(|edit| edit.apply(&file_text).into_bytes()));
+    }
     let result = super::format_text(request.file_path, &file_text, request.config).map_err(FormatError::new)?;
     Ok(result.map(|file_text| file_text.into_bytes()))
   }
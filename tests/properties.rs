@@ -0,0 +1,526 @@
+//! Property tests that format randomly generated Dockerfiles and check that:
+//!
+//! 1. the output parses,
+//! 2. formatting the output again is a no-op, and
+//! 3. the instructions are semantically unchanged, as checked by the crate's
+//!    own `verifySemantics` comparison.
+//!
+//! The instructions are generated as AST values and printed with random
+//! keyword case, spacing, line continuations and comments.
+//!
+//! Each case is generated from a seed, so a failure can be reproduced with
+//! `PROPERTY_SEED=<seed> cargo test --test properties`. Set `PROPERTY_CASES`
+//! to run more cases than the default.
+
+use std::mem::discriminant;
+use std::path::Path;
+
+use dprint_plugin_dockerfile::FormatError;
+use dprint_plugin_dockerfile::ast::*;
+use dprint_plugin_dockerfile::configuration::CommandForm;
+use dprint_plugin_dockerfile::configuration::Configuration;
+use dprint_plugin_dockerfile::configuration::ConfigurationBuilder;
+use dprint_plugin_dockerfile::configuration::KeyValueLayout;
+use dprint_plugin_dockerfile::configuration::QuoteStyle;
+use dprint_plugin_dockerfile::format_text;
+
+const DEFAULT_CASES: u64 = 500;
+
+#[test]
+fn formatting_is_stable_and_preserves_semantics() {
+  // every config verifies the semantics, which is the third property
+  let configs = [
+    ConfigurationBuilder::new()
+      .verify_semantics(true)
+      .sort_flags(true)
+      .normalize_healthcheck_options(true)
+      .build(),
+    ConfigurationBuilder::new()
+      .verify_semantics(true)
+      .line_width(40)
+      .healthcheck_cmd_new_line(true)
+      .key_value_layout(KeyValueLayout::FitOrBreakAll)
+      .quote_style(QuoteStyle::Minimal)
+      .build(),
+    ConfigurationBuilder::new()
+      .verify_semantics(true)
+      .blank_line_between_stages(true)
+      .max_consecutive_blank_lines(0)
+      .copy_destination_new_line(true)
+      .cmd_form(CommandForm::Exec)
+      .entrypoint_form(CommandForm::Exec)
+      .build(),
+    ConfigurationBuilder::new().verify_semantics(true).shell_formatting(true).build(),
+  ];
+  let seeds = match std::env::var("PROPERTY_SEED") {
+    Ok(seed) => vec![seed.parse::<u64>().expect("PROPERTY_SEED should be a number")],
+    Err(_) => {
+      let cases = std::env::var("PROPERTY_CASES").map(|cases| cases.parse().unwrap()).unwrap_or(DEFAULT_CASES);
+      (1..=cases).collect()
+    }
+  };
+  for seed in seeds {
+    let (instructions, text) = generate_dockerfile(&mut Rng(seed));
+    let config = &configs[seed as usize % configs.len()];
+    if let Err(message) = check_properties(&instructions, &text, config) {
+      panic!("seed {seed}: {message}\n==== input ====\n{text}");
+    }
+  }
+}
+
+fn check_properties(instructions: &[Instruction], text: &str, config: &Configuration) -> Result<(), String> {
+  let path = Path::new("Dockerfile");
+  let input = Dockerfile::parse(text).map_err(|err| format!("generated input doesn't parse: {err}"))?;
+  let kinds = |instructions: &[Instruction]| instructions.iter().map(discriminant).collect::<Vec<_>>();
+  if kinds(&input.instructions) != kinds(instructions) {
+    return Err(format!(
+      "generated input doesn't parse to the generated instructions\n  generated: {instructions:?}\n  parsed:    {:?}",
+      input.instructions
+    ));
+  }
+  let formatted = match format_text(path, text, config) {
+    Ok(formatted) => formatted.unwrap_or_else(|| text.to_string()),
+    Err(err @ FormatError::SemanticChange { .. }) => return Err(err.to_string()),
+    Err(err) => return Err(format!("failed to format: {err}")),
+  };
+  Dockerfile::parse(&formatted).map_err(|err| format!("output doesn't parse: {err}\n==== output ====\n{formatted}"))?;
+  let reformatted = format_text(path, &formatted, config).map_err(|err| format!("failed to format the output: {err}"))?;
+  if let Some(reformatted) = reformatted {
+    return Err(format!(
+      "formatting isn't stable\n==== output ====\n{formatted}\n==== formatted again ====\n{reformatted}"
+    ));
+  }
+  Ok(())
+}
+
+// ---- generation ----
+
+/// A small deterministic xorshift generator, so each case is reproducible from
+/// its seed without extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+  fn next(&mut self) -> u64 {
+    // avoid the all-zero state and decorrelate nearby seeds
+    let mut x = self.0.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    self.0 = x;
+    x
+  }
+
+  fn below(&mut self, n: usize) -> usize {
+    (self.next() % n as u64) as usize
+  }
+
+  fn chance(&mut self, percent: usize) -> bool {
+    self.below(100) < percent
+  }
+
+  fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+    items[self.below(items.len())]
+  }
+}
+
+/// Generates the instructions of a Dockerfile and prints them, with comments
+/// and blank lines between them.
+fn generate_dockerfile(rng: &mut Rng) -> (Vec<Instruction>, String) {
+  let mut instructions = Vec::new();
+  let mut text = String::new();
+  if rng.chance(20) {
+    text.push_str("# syntax=docker/dockerfile:1\n");
+  }
+  for _ in 0..1 + rng.below(3) {
+    let from = from(rng);
+    push_line(rng, &mut text, &from);
+    instructions.push(from);
+    for _ in 0..rng.below(8) {
+      if rng.chance(15) {
+        text.push_str(&format!("#{}{}\n", rng.pick(&["", " ", "   "]), rng.pick(&["a comment", "TODO: tidy", ""])));
+      }
+      if rng.chance(15) {
+        text.push_str(&"\n".repeat(1 + rng.below(3)));
+      }
+      let instruction = instruction(rng);
+      push_line(rng, &mut text, &instruction);
+      instructions.push(instruction);
+    }
+  }
+  (instructions, text)
+}
+
+fn push_line(rng: &mut Rng, text: &mut String, instruction: &Instruction) {
+  text.push_str(rng.pick(&["", "", "", " ", "  "]));
+  let printed = print_instruction(rng, instruction);
+  text.push_str(&printed);
+  // a heredoc's closing delimiter has to be the whole line
+  if !matches!(instruction, Instruction::Heredoc(_)) {
+    text.push_str(rng.pick(&["", "", "", " ", "\t"]));
+  }
+  text.push('\n');
+}
+
+/// A string for the AST. Spans aren't printed, so they're all empty.
+fn string(content: &str) -> SpannedString {
+  SpannedString {
+    span: Span::new(0, 0),
+    content: content.to_string(),
+  }
+}
+
+fn breakable(components: Vec<BreakableStringComponent>) -> BreakableString {
+  BreakableString {
+    span: Span::new(0, 0),
+    components,
+  }
+}
+
+fn from(rng: &mut Rng) -> Instruction {
+  let flags = match rng.chance(20) {
+    true => vec![FromFlag {
+      span: Span::new(0, 0),
+      name: string("platform"),
+      value: string("linux/amd64"),
+    }],
+    false => Vec::new(),
+  };
+  Instruction::From(FromInstruction {
+    span: Span::new(0, 0),
+    flags,
+    image: string(rng.pick(&["alpine", "alpine:3.20", "node:20-slim", "${BASE_IMAGE}", "scratch", "build"])),
+    alias: rng.chance(40).then(|| string(rng.pick(&["build", "runtime", "deps"]))),
+  })
+}
+
+fn instruction(rng: &mut Rng) -> Instruction {
+  let span = Span::new(0, 0);
+  match rng.below(15) {
+    0 => Instruction::Arg(ArgInstruction {
+      span,
+      name: string(rng.pick(&["VERSION", "TARGETOS", "NODE_ENV"])),
+      value: rng.chance(50).then(|| string(value(rng))),
+    }),
+    1 => Instruction::Label(LabelInstruction {
+      span,
+      labels: (0..1 + rng.below(3))
+        .map(|_| Label {
+          span,
+          name: string(rng.pick(&["version", "org.opencontainers.image.title", "\"quoted key\""])),
+          value: string(value(rng)),
+        })
+        .collect(),
+    }),
+    2 | 3 => Instruction::Run(RunInstruction { span, expr: command(rng) }),
+    4 => Instruction::Cmd(CmdInstruction { span, expr: command(rng) }),
+    5 => Instruction::Entrypoint(EntrypointInstruction { span, expr: command(rng) }),
+    6 => Instruction::Shell(ShellInstruction {
+      span,
+      expr: ShellOrExecExpr::Exec(exec_form(rng)),
+    }),
+    7 => copy(rng),
+    8 => Instruction::Env(EnvInstruction {
+      span,
+      vars: (0..1 + rng.below(3))
+        .map(|_| EnvVar {
+          span,
+          key: string(rng.pick(&["PATH", "NODE_ENV", "A"])),
+          value: breakable(vec![BreakableStringComponent::String(string(value(rng)))]),
+        })
+        .collect(),
+    }),
+    9 => {
+      let (flags, cmd) = if rng.chance(15) {
+        (Vec::new(), None)
+      } else {
+        let flags = [("interval", "30s"), ("timeout", "3s"), ("retries", "3")]
+          .into_iter()
+          .filter(|_| rng.chance(40))
+          .map(|(name, value)| HealthcheckFlag {
+            span,
+            name: string(name),
+            value: string(value),
+          })
+          .collect();
+        (flags, Some(Box::new(Instruction::Cmd(CmdInstruction { span, expr: command(rng) }))))
+      };
+      Instruction::Healthcheck(HealthcheckInstruction { span, flags, cmd })
+    }
+    10 => {
+      let trigger = match rng.below(3) {
+        0 => Instruction::Run(RunInstruction { span, expr: command(rng) }),
+        1 => copy(rng),
+        _ => misc("WORKDIR", "/app"),
+      };
+      Instruction::Onbuild(OnbuildInstruction {
+        span,
+        instruction: Box::new(trigger),
+      })
+    }
+    11 => {
+      let start = format!("<<EOF{}", rng.pick(&["", " sh", " cat"]));
+      Instruction::Heredoc(HeredocInstruction {
+        span,
+        instruction: Box::new(Instruction::Run(RunInstruction {
+          span,
+          expr: ShellOrExecExpr::Shell(breakable(vec![BreakableStringComponent::String(string(&start))])),
+        })),
+        body: format!("{}EOF", rng.pick(&["echo hello\n", "  set -e\n  apk add  curl\n", "#!/bin/sh\n\necho $HOME\n"])),
+      })
+    }
+    _ => match rng.below(7) {
+      0 => misc("EXPOSE", "80 443/tcp"),
+      1 => misc("WORKDIR", "/app"),
+      2 => misc("USER", "node:node"),
+      3 => misc("VOLUME", "[\"/data\"]"),
+      4 => misc("STOPSIGNAL", "SIGTERM"),
+      5 => Instruction::Add(AddInstruction {
+        span,
+        flags: Vec::new(),
+        args: CopyArgs::Paths {
+          sources: vec![string("https://example.com/a.tar.gz")],
+          destination: string("/tmp/"),
+        },
+      }),
+      _ => misc("MAINTAINER", "someone <someone@example.com>"),
+    },
+  }
+}
+
+fn misc(name: &str, arguments: &str) -> Instruction {
+  Instruction::Misc(MiscInstruction {
+    span: Span::new(0, 0),
+    instruction: string(name),
+    arguments: breakable(vec![BreakableStringComponent::String(string(arguments))]),
+  })
+}
+
+fn copy(rng: &mut Rng) -> Instruction {
+  let span = Span::new(0, 0);
+  let mut flags = Vec::new();
+  if rng.chance(30) {
+    flags.push(CopyFlag {
+      span,
+      name: string("from"),
+      value: string(rng.pick(&["build", "deps"])),
+    });
+  }
+  if rng.chance(20) {
+    flags.push(CopyFlag {
+      span,
+      name: string("chown"),
+      value: string("app:app"),
+    });
+  }
+  let args = if rng.chance(20) {
+    CopyArgs::Exec(exec_form(rng))
+  } else {
+    CopyArgs::Paths {
+      sources: (0..1 + rng.below(3))
+        .map(|_| string(rng.pick(&["package.json", "src/", "./dist", "*.lock", "\"with space\""])))
+        .collect(),
+      destination: string(rng.pick(&["/app/", ".", "/usr/src/app"])),
+    }
+  };
+  if rng.chance(25) {
+    Instruction::Add(AddInstruction { span, flags, args })
+  } else {
+    Instruction::Copy(CopyInstruction { span, flags, args })
+  }
+}
+
+fn value(rng: &mut Rng) -> &'static str {
+  rng.pick(&[
+    "1.0",
+    "production",
+    "\"two  words\"",
+    "'single'",
+    "$PATH:/usr/local/bin",
+    "${VERSION:-latest}",
+    "\"\"",
+  ])
+}
+
+/// A command in exec form, or in shell form as lines joined by continuations
+/// with comments sometimes between them.
+fn command(rng: &mut Rng) -> ShellOrExecExpr {
+  if rng.chance(30) {
+    return ShellOrExecExpr::Exec(exec_form(rng));
+  }
+  let mut components = Vec::new();
+  let mut line = String::new();
+  for i in 0..1 + rng.below(4) {
+    if i > 0 {
+      line.push_str(rng.pick(&[" ", "  ", "\t"]));
+      line.push_str(rng.pick(&["&&", "||", "|", ";", "&&"]));
+      if rng.chance(25) {
+        components.push(BreakableStringComponent::String(string(&std::mem::take(&mut line))));
+        if rng.chance(30) {
+          components.push(BreakableStringComponent::Comment(SpannedComment {
+            span: Span::new(0, 0),
+            content: rng.pick(&["# a comment", "#   indented", "#"]).to_string(),
+          }));
+        }
+      } else {
+        line.push_str(rng.pick(&[" ", "  ", "\t"]));
+      }
+    }
+    line.push_str(rng.pick(&[
+      "apk add --no-cache curl",
+      "echo \"hello   world\"",
+      "npm ci",
+      "rm -rf /var/cache/apk/*",
+      "echo 'a  b'",
+      "cd /app",
+      "make   -j4",
+      "test -f \"$FILE\"",
+      "node app.js",
+      "nginx -g 'daemon off;'",
+    ]));
+  }
+  components.push(BreakableStringComponent::String(string(&line)));
+  ShellOrExecExpr::Shell(breakable(components))
+}
+
+fn exec_form(rng: &mut Rng) -> StringArray {
+  StringArray {
+    span: Span::new(0, 0),
+    elements: (0..1 + rng.below(3))
+      .map(|_| string(rng.pick(&["sh", "-c", "echo hi", "/bin/bash", "npm", "start", "a \"quoted\" word"])))
+      .collect(),
+  }
+}
+
+// ---- printing ----
+
+fn print_instruction(rng: &mut Rng, instruction: &Instruction) -> String {
+  match instruction {
+    Instruction::From(node) => {
+      let mut parts = vec![keyword(rng, "FROM")];
+      parts.extend(node.flags.iter().map(|flag| format!("--{}={}", flag.name.content, flag.value.content)));
+      parts.push(node.image.content.clone());
+      if let Some(alias) = &node.alias {
+        parts.push(keyword(rng, "AS"));
+        parts.push(alias.content.clone());
+      }
+      join(rng, &parts)
+    }
+    Instruction::Arg(node) => {
+      let value = node.value.as_ref().map(|value| format!("={}", value.content)).unwrap_or_default();
+      format!("{} {}{}", keyword(rng, "ARG"), node.name.content, value)
+    }
+    Instruction::Label(node) => {
+      let mut parts = vec![keyword(rng, "LABEL")];
+      parts.extend(node.labels.iter().map(|label| format!("{}={}", label.name.content, label.value.content)));
+      join(rng, &parts)
+    }
+    Instruction::Run(node) => format!("{} {}", keyword(rng, "RUN"), print_expr(&node.expr)),
+    Instruction::Cmd(node) => format!("{} {}", keyword(rng, "CMD"), print_expr(&node.expr)),
+    Instruction::Entrypoint(node) => format!("{} {}", keyword(rng, "ENTRYPOINT"), print_expr(&node.expr)),
+    Instruction::Shell(node) => format!("{} {}", keyword(rng, "SHELL"), print_expr(&node.expr)),
+    Instruction::Copy(node) => print_copy(rng, "COPY", &node.flags, &node.args),
+    Instruction::Add(node) => print_copy(rng, "ADD", &node.flags, &node.args),
+    Instruction::Env(node) => {
+      let mut parts = vec![keyword(rng, "ENV")];
+      parts.extend(node.vars.iter().map(|var| format!("{}={}", var.key.content, print_breakable(&var.value))));
+      join(rng, &parts)
+    }
+    Instruction::Onbuild(node) => format!(
+      "{}{}{}",
+      keyword(rng, "ONBUILD"),
+      rng.pick(&[" ", "  "]),
+      print_instruction(rng, &node.instruction)
+    ),
+    Instruction::Healthcheck(node) => {
+      let mut parts = vec![keyword(rng, "HEALTHCHECK")];
+      parts.extend(node.flags.iter().map(|flag| format!("--{}={}", flag.name.content, flag.value.content)));
+      match &node.cmd {
+        Some(cmd) => parts.push(print_instruction(rng, cmd)),
+        None => parts.push("NONE".to_string()),
+      }
+      join(rng, &parts)
+    }
+    Instruction::Heredoc(node) => format!("{}\n{}", print_instruction(rng, &node.instruction), node.body),
+    Instruction::Misc(node) => {
+      let arguments = print_breakable(&node.arguments);
+      let arguments = arguments.split(' ').map(String::from).collect::<Vec<_>>();
+      format!("{}{}{}", keyword(rng, &node.instruction.content), ws(rng), join(rng, &arguments))
+    }
+    Instruction::Unknown(node) => node.content.clone(),
+  }
+}
+
+fn print_copy(rng: &mut Rng, name: &str, flags: &[CopyFlag], args: &CopyArgs) -> String {
+  let mut parts = vec![keyword(rng, name)];
+  parts.extend(flags.iter().map(|flag| format!("--{}={}", flag.name.content, flag.value.content)));
+  match args {
+    CopyArgs::Paths { sources, destination } => {
+      parts.extend(sources.iter().map(|source| source.content.clone()));
+      parts.push(destination.content.clone());
+    }
+    CopyArgs::Exec(array) => parts.push(print_array(array)),
+  }
+  join(rng, &parts)
+}
+
+fn print_expr(expr: &ShellOrExecExpr) -> String {
+  match expr {
+    ShellOrExecExpr::Shell(value) => print_breakable(value),
+    ShellOrExecExpr::Exec(array) => print_array(array),
+  }
+}
+
+/// Prints the lines of a breakable string joined by line continuations, with
+/// its comments on lines of their own.
+fn print_breakable(value: &BreakableString) -> String {
+  let mut text = String::new();
+  for (i, component) in value.components.iter().enumerate() {
+    let after_string = i > 0 && matches!(value.components[i - 1], BreakableStringComponent::String(_));
+    match component {
+      BreakableStringComponent::String(line) => {
+        text.push_str(if after_string {
+          " \\\n  "
+        } else if i > 0 {
+          "  "
+        } else {
+          ""
+        });
+        text.push_str(&line.content);
+      }
+      BreakableStringComponent::Comment(comment) => {
+        text.push_str(if after_string { " \\\n" } else { "" });
+        text.push_str(&format!("  {}\n", comment.content));
+      }
+    }
+  }
+  text
+}
+
+fn print_array(array: &StringArray) -> String {
+  let elements = array
+    .elements
+    .iter()
+    .map(|element| serde_json::to_string(&element.content).unwrap())
+    .collect::<Vec<_>>();
+  format!("[{}]", elements.join(", "))
+}
+
+fn keyword(rng: &mut Rng, name: &str) -> String {
+  if rng.chance(10) { name.to_lowercase() } else { name.to_string() }
+}
+
+/// Whitespace between arguments, sometimes continued onto the next line.
+fn ws(rng: &mut Rng) -> &'static str {
+  rng.pick(&[" ", " ", " ", "  ", "\t", " \\\n  ", " \\\n    "])
+}
+
+fn join(rng: &mut Rng, parts: &[String]) -> String {
+  let mut result = String::new();
+  for (i, part) in parts.iter().enumerate() {
+    if i > 0 {
+      result.push_str(ws(rng));
+    }
+    result.push_str(part);
+  }
+  result
+}
@@ -49,3 +49,126 @@ fn test_specs() {
     }),
   )
 }
+
+/// Keeps `deployment/ast.schema.json` in sync with the shape of the serialized
+/// AST by validating the AST of every spec's input and output against it.
+#[cfg(feature = "serde")]
+#[test]
+fn spec_inputs_match_ast_schema() {
+  use dprint_plugin_dockerfile::ast::Dockerfile;
+
+  let schema = serde_json::from_str::<serde_json::Value>(include_str!("../deployment/ast.schema.json")).unwrap();
+  let mut count = 0;
+  for entry in walkdir(&PathBuf::from("./tests/specs")) {
+    let file_text = std::fs::read_to_string(&entry).unwrap();
+    let options = ParseSpecOptions {
+      default_file_name: "file.dockerfile",
+    };
+    for spec in parse_specs(file_text, &options) {
+      for text in [&spec.file_text, &spec.expected_text] {
+        // texts that fail to parse are errors rather than ASTs
+        let Ok(file) = Dockerfile::parse(text) else { continue };
+        let json = serde_json::to_value(&file).unwrap();
+        if let Err(err) = validate(&json, &schema, &schema, "") {
+          panic!("{} ({}): {}", entry.display(), spec.message, err);
+        }
+        count += 1;
+      }
+    }
+  }
+  assert!(count > 300, "only validated {count} spec texts");
+}
+
+#[cfg(feature = "serde")]
+fn walkdir(dir: &std::path::Path) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  for entry in std::fs::read_dir(dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.is_dir() {
+      files.extend(walkdir(&path));
+    } else {
+      files.push(path);
+    }
+  }
+  files
+}
+
+/// Validates `value` against a JSON schema, supporting the draft-07 keywords
+/// that `ast.schema.json` uses.
+#[cfg(feature = "serde")]
+fn validate(value: &serde_json::Value, schema: &serde_json::Value, root: &serde_json::Value, path: &str) -> Result<(), String> {
+  use serde_json::Value;
+
+  if let Some(reference) = schema["$ref"].as_str() {
+    let definition = reference
+      .strip_prefix("#/definitions/")
+      .ok_or_else(|| format!("unsupported $ref {reference}"))?;
+    validate(value, &root["definitions"][definition], root, path)?;
+  }
+  if let Some(expected) = schema["type"].as_str() {
+    let matches = match expected {
+      "object" => value.is_object(),
+      "array" => value.is_array(),
+      "string" => value.is_string(),
+      "integer" => value.is_u64() || value.is_i64(),
+      "boolean" => value.is_boolean(),
+      "null" => value.is_null(),
+      _ => return Err(format!("unsupported type {expected}")),
+    };
+    if !matches {
+      return Err(format!("{path}: expected {expected}, found {value}"));
+    }
+  }
+  if let Some(expected) = schema.get("const")
+    && value != expected
+  {
+    return Err(format!("{path}: expected {expected}, found {value}"));
+  }
+  if let Some(allowed) = schema["enum"].as_array()
+    && !allowed.contains(value)
+  {
+    return Err(format!("{path}: {value} isn't one of {allowed:?}"));
+  }
+  if let (Some(minimum), Some(number)) = (schema["minimum"].as_u64(), value.as_u64())
+    && number < minimum
+  {
+    return Err(format!("{path}: {number} is less than {minimum}"));
+  }
+  if let Some(object) = value.as_object() {
+    for name in schema["required"].as_array().into_iter().flatten() {
+      let name = name.as_str().unwrap();
+      if !object.contains_key(name) {
+        return Err(format!("{path}: missing {name}"));
+      }
+    }
+    for (name, property) in object {
+      match schema["properties"].get(name) {
+        Some(property_schema) => validate(property, property_schema, root, &format!("{path}.{name}"))?,
+        None if schema["additionalProperties"] == Value::Bool(false) => return Err(format!("{path}: unexpected property {name}")),
+        None => {}
+      }
+    }
+  }
+  if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+    for (index, item) in array.iter().enumerate() {
+      validate(item, items, root, &format!("{path}[{index}]"))?;
+    }
+  }
+  for sub_schema in schema["allOf"].as_array().into_iter().flatten() {
+    validate(value, sub_schema, root, path)?;
+  }
+  if let Some(variants) = schema["oneOf"].as_array() {
+    let errors = variants
+      .iter()
+      .filter_map(|variant| validate(value, variant, root, path).err())
+      .collect::<Vec<_>>();
+    if errors.len() != variants.len() - 1 {
+      return Err(format!(
+        "{path}: matched {} of oneOf, not one ({})",
+        variants.len() - errors.len(),
+        errors.join("; ")
+      ));
+    }
+  }
+  Ok(())
+}